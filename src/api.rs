@@ -1,5 +1,6 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 use crate::utils::{empty_array_is_map, is_false, is_zero, string_or_u32, string_vec};
 
@@ -131,6 +132,44 @@ pub struct Item {
     pub requirements: Vec<ItemProperty>,
 }
 
+/// Response of the legacy `get-stash-items` endpoint of the character window.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StashItemsResponse {
+    pub num_tabs: u32,
+    /// Only included when requested with `tabs`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tabs: Vec<LegacyStashTab>,
+    #[serde(default)]
+    pub items: Vec<Item>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub quad_layout: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LegacyStashTab {
+    pub id: String,
+    #[serde(rename = "n")]
+    pub name: String,
+    #[serde(rename = "i")]
+    pub index: u32,
+    #[serde(rename = "type")]
+    pub stash_type: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub hidden: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub selected: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colour: Option<LegacyStashTabColour>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LegacyStashTabColour {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ItemProperty {
@@ -227,6 +266,111 @@ pub struct PassivesResponse {
     pub skill_tree_data: Option<SkillTreeData>,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CharactersResponse {
+    pub characters: Vec<Character>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CharacterResponse {
+    pub character: Character,
+}
+
+/// A character as returned by the OAuth character endpoints.
+///
+/// Equipment, inventory, jewels and passives are only included for a single character.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Character {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub realm: Realm,
+    pub class: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub league: Option<String>,
+    pub level: u32,
+    pub experience: u64,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub ruthless: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub expired: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub deleted: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub current: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub equipment: Vec<Item>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inventory: Vec<Item>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub jewels: Vec<Item>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passives: Option<CharacterPassives>,
+}
+
+impl Character {
+    /// Combines the responses of the legacy character window endpoints into a [`Character`].
+    pub fn from_legacy(items: ItemsResponse, passives: PassivesResponse) -> Self {
+        let (inventory, equipment) = items
+            .items
+            .into_iter()
+            .partition(|item| item.inventory_id.as_deref() == Some("MainInventory"));
+
+        Self {
+            id: String::new(),
+            name: items.character.name,
+            // the character window does not include the realm
+            realm: Realm::default(),
+            class: items.character.class,
+            league: Some(items.character.league),
+            level: items.character.level,
+            experience: items.character.experience,
+            ruthless: false,
+            expired: false,
+            deleted: false,
+            current: items.character.last_active,
+            equipment,
+            inventory,
+            jewels: passives.items,
+            passives: Some(CharacterPassives {
+                hashes: passives.hashes,
+                hashes_ex: passives.hashes_ex,
+                mastery_effects: Map::new(),
+                bandit_choice: None,
+                pantheon_major: None,
+                pantheon_minor: None,
+                jewel_data: passives.jewel_data,
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CharacterPassives {
+    pub hashes: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hashes_ex: Vec<u32>,
+    #[serde(
+        default,
+        deserialize_with = "empty_array_is_map",
+        skip_serializing_if = "Map::is_empty"
+    )]
+    pub mastery_effects: Map<String, u32>,
+    #[serde(rename = "banditChoice", skip_serializing_if = "Option::is_none")]
+    pub bandit_choice: Option<String>,
+    #[serde(rename = "pantheonMajor", skip_serializing_if = "Option::is_none")]
+    pub pantheon_major: Option<String>,
+    #[serde(rename = "pantheonMinor", skip_serializing_if = "Option::is_none")]
+    pub pantheon_minor: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "empty_array_is_map",
+        skip_serializing_if = "Map::is_empty"
+    )]
+    pub jewel_data: Map<String, JewelData>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct JewelData {
@@ -449,26 +593,168 @@ pub struct Rect {
     pub height: u32,
 }
 
+/// A realm (platform) of the game.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", from = "String")]
+pub enum Realm {
+    #[default]
+    Pc,
+    Xbox,
+    Sony,
+    Poe2,
+    /// A realm unknown to this library.
+    Other(String),
+}
+
+impl Realm {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Realm::Pc => "pc",
+            Realm::Xbox => "xbox",
+            Realm::Sony => "sony",
+            Realm::Poe2 => "poe2",
+            Realm::Other(realm) => realm,
+        }
+    }
+}
+
+impl fmt::Display for Realm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&str> for Realm {
+    fn from(realm: &str) -> Self {
+        match realm {
+            "pc" => Realm::Pc,
+            "xbox" => Realm::Xbox,
+            "sony" => Realm::Sony,
+            "poe2" => Realm::Poe2,
+            other => Realm::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for Realm {
+    fn from(realm: String) -> Self {
+        Self::from(realm.as_str())
+    }
+}
+
+impl From<Realm> for String {
+    fn from(realm: Realm) -> Self {
+        realm.as_str().to_string()
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct League {
     pub id: String,
-    pub realm: String,
+    #[serde(default)]
+    pub realm: Realm,
+    #[serde(default)]
     pub description: String,
-    pub register_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<LeagueCategory>,
+    #[serde(default)]
+    pub rules: Vec<LeagueRule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub register_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub event: bool,
+    #[serde(default)]
     pub url: String,
-    pub start_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_at: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub end_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub timed_event: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub score_event: bool,
+    #[serde(default)]
     pub delve_event: bool,
-    pub rules: Vec<LeagueRule>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub ancestor_event: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub league_event: bool,
+    #[serde(alias = "ladder_url", skip_serializing_if = "Option::is_none")]
+    pub ladder_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub private_league_url: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LeagueCategory {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub current: bool,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LeagueRule {
     pub id: String,
     pub name: String,
-    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// The type of leagues to list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LeagueType {
+    Main,
+    Event,
+    Season,
+}
+
+impl LeagueType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LeagueType::Main => "main",
+            LeagueType::Event => "event",
+            LeagueType::Season => "season",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LeagueResponse {
+    pub league: League,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LeaguesResponse {
+    pub leagues: Vec<League>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Profile {
+    pub uuid: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub realm: Option<Realm>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guild: Option<ProfileGuild>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub twitch: Option<ProfileTwitch>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProfileGuild {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    pub name: String,
+    pub tag: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProfileTwitch {
+    pub name: String,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -488,11 +774,51 @@ impl Default for LadderResponse {
     }
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LeagueLadderResponse {
+    pub league: League,
+    pub ladder: LadderResponse,
+}
+
+/// The order of a ladder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LadderSort {
+    Xp,
+    Depth,
+    DepthSolo,
+    Ancestor,
+    Time,
+    Score,
+    Class,
+}
+
+impl LadderSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LadderSort::Xp => "xp",
+            LadderSort::Depth => "depth",
+            LadderSort::DepthSolo => "depthsolo",
+            LadderSort::Ancestor => "ancestor",
+            LadderSort::Time => "time",
+            LadderSort::Score => "score",
+            LadderSort::Class => "class",
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LadderEntry {
     pub rank: i32,
+    #[serde(default, skip_serializing_if = "is_false")]
     pub dead: bool,
     #[serde(default, skip_serializing_if = "is_false")]
+    pub retired: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub ineligible: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub public: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
     pub online: bool,
     pub character: LadderEntryCharacter,
     pub account: LadderEntryAccount,
@@ -504,11 +830,456 @@ pub struct LadderEntryCharacter {
     pub name: String,
     pub level: u32,
     pub class: String,
+    #[serde(default)]
     pub experience: u64,
+    /// Time in seconds, for race ladders.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth: Option<LadderEntryDepth>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LadderEntryDepth {
+    #[serde(default)]
+    pub default: u32,
+    #[serde(default)]
+    pub solo: u32,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LadderEntryAccount {
     pub name: String,
-    pub realm: String,
+    #[serde(default)]
+    pub realm: Realm,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guild: Option<ProfileGuild>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub challenges: Option<LadderEntryChallenges>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub twitch: Option<LadderEntryTwitch>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LadderEntryChallenges {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub set: Option<String>,
+    pub completed: u32,
+    pub max: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LadderEntryTwitch {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<TwitchStream>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TwitchStream {
+    pub name: String,
+    #[serde(default)]
+    pub image: String,
+    #[serde(default)]
+    pub status: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EventLadderResponse {
+    pub league: League,
+    pub ladder: EventLadder,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EventLadder {
+    pub total: usize,
+    pub entries: Vec<EventLadderEntry>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EventLadderEntry {
+    pub rank: u32,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub ineligible: bool,
+    /// Time in seconds it took to finish the event.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<u32>,
+    pub private_league: PrivateLeague,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PrivateLeague {
+    pub name: String,
+    pub url: String,
+}
+
+/// The type of PvP matches to list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PvpMatchType {
+    Upcoming,
+    Season,
+    League,
+}
+
+impl PvpMatchType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PvpMatchType::Upcoming => "upcoming",
+            PvpMatchType::Season => "season",
+            PvpMatchType::League => "league",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PvpMatchesResponse {
+    pub matches: Vec<PvpMatch>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PvpMatchResponse {
+    #[serde(rename = "match")]
+    pub pvp_match: PvpMatch,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PvpMatch {
+    pub id: String,
+    #[serde(default)]
+    pub realm: Realm,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub glicko_ratings: bool,
+    #[serde(default)]
+    pub pvp: bool,
+    /// `Blitz`, `Swiss` or `Arena`.
+    #[serde(default)]
+    pub style: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub register_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub complete: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub upcoming: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub in_progress: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PvpLadderResponse {
+    #[serde(rename = "match")]
+    pub pvp_match: PvpMatch,
+    pub ladder: PvpLadder,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PvpLadder {
+    pub total: usize,
+    pub entries: Vec<PvpLadderTeamEntry>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PvpLadderTeamEntry {
+    pub rank: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rating: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub points: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub games_played: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cumulative_opponent_points: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_game_time: Option<DateTime<Utc>>,
+    pub members: Vec<PvpLadderTeamMember>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PvpLadderTeamMember {
+    pub account: LadderEntryAccount,
+    pub character: PvpCharacter,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub public: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PvpCharacter {
+    pub id: String,
+    pub name: String,
+    pub level: u32,
+    pub class: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub league: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<u32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemFiltersResponse {
+    pub filters: Vec<ItemFilter>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemFilterResponse {
+    pub filter: ItemFilter,
+}
+
+/// An item filter of the account.
+///
+/// The `id` and `validation` are assigned by the API and ignored when creating
+/// or updating a filter.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ItemFilter {
+    #[serde(default, skip_serializing)]
+    pub id: String,
+    pub filter_name: String,
+    #[serde(default)]
+    pub realm: Realm,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default, rename = "type")]
+    pub filter_type: ItemFilterType,
+    #[serde(default)]
+    pub public: bool,
+    /// The filter itself, only included when requesting a single filter.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub validation: Option<ItemFilterValidation>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ItemFilterType {
+    #[default]
+    Normal,
+    Ruthless,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemFilterValidation {
+    pub valid: bool,
+    /// The game version the filter was validated against.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validated: Option<DateTime<Utc>>,
+}
+
+impl fmt::Display for ItemFilterValidation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.valid { "valid" } else { "not valid" })?;
+        if let Some(version) = &self.version {
+            write!(f, " for game version {version}")?;
+        }
+        if let Some(validated) = &self.validated {
+            write!(f, " (validated at {})", validated.to_rfc3339())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PublicStashesResponse {
+    pub next_change_id: String,
+    pub stashes: Vec<PublicStashChange>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicStashChange {
+    pub id: String,
+    pub public: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stash: Option<String>,
+    pub stash_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub league: Option<String>,
+    #[serde(default)]
+    pub items: Vec<Item>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StashesResponse {
+    pub stashes: Vec<StashTab>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StashResponse {
+    pub stash: StashTab,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StashTab {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub stash_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<u32>,
+    #[serde(default)]
+    pub metadata: StashTabMetadata,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<StashTab>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<Item>,
+}
+
+impl StashTab {
+    /// Iterates over this stash tab and all of its children recursively, depth first.
+    ///
+    /// Children are the tabs of a folder and the substashes of special tabs, e.g. the map stash.
+    pub fn iter(&self) -> StashTabIter<'_> {
+        StashTabIter { stack: vec![self] }
+    }
+}
+
+/// Depth first iterator over a [`StashTab`] and its children, created with [`StashTab::iter`].
+#[derive(Clone, Debug)]
+pub struct StashTabIter<'a> {
+    stack: Vec<&'a StashTab>,
+}
+
+impl<'a> Iterator for StashTabIter<'a> {
+    type Item = &'a StashTab;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tab = self.stack.pop()?;
+        self.stack.extend(tab.children.iter().rev());
+        Some(tab)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StashTabMetadata {
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub public: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub folder: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colour: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CurrencyExchangeResponse {
+    /// Unix timestamp of the next hour to request.
+    pub next_change_id: u64,
+    pub markets: Vec<CurrencyExchangeMarket>,
+}
+
+/// Trading activity of a currency pair during one hour.
+///
+/// All maps are keyed by the currency id of either side of the market.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CurrencyExchangeMarket {
+    pub league: String,
+    /// The traded currency pair, e.g. `chaos|divine`.
+    pub market_id: String,
+    #[serde(default)]
+    pub volume_traded: Map<String, u64>,
+    #[serde(default)]
+    pub lowest_stock: Map<String, u64>,
+    #[serde(default)]
+    pub highest_stock: Map<String, u64>,
+    #[serde(default)]
+    pub lowest_ratio: Map<String, u64>,
+    #[serde(default)]
+    pub highest_ratio: Map<String, u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(type_line: &str, inventory_id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "verified": false,
+            "w": 1,
+            "h": 1,
+            "icon": "https://web.poecdn.com/image.png",
+            "name": "",
+            "typeLine": type_line,
+            "baseType": type_line,
+            "identified": true,
+            "ilvl": 84,
+            "inventoryId": inventory_id,
+        })
+    }
+
+    #[test]
+    fn unknown_realm() {
+        let league: League =
+            serde_json::from_value(serde_json::json!({ "id": "Standard", "realm": "switch" }))
+                .unwrap();
+        assert_eq!(Realm::Other("switch".to_string()), league.realm);
+        assert_eq!("switch", serde_json::to_value(&league).unwrap()["realm"]);
+
+        assert_eq!(Realm::Poe2, Realm::from("poe2"));
+        assert_eq!("\"xbox\"", serde_json::to_string(&Realm::Xbox).unwrap());
+    }
+
+    #[test]
+    fn character_from_legacy() {
+        let items: ItemsResponse = serde_json::from_value(serde_json::json!({
+            "items": [
+                item("Vaal Regalia", "BodyArmour"),
+                item("Portal Scroll", "MainInventory"),
+                item("Onyx Amulet", "Amulet"),
+            ],
+            "character": {
+                "ascendancyClass": 1,
+                "class": "Necromancer",
+                "classId": 5,
+                "experience": 4250334444u64,
+                "lastActive": true,
+                "league": "Standard",
+                "level": 100,
+                "name": "SteelDD",
+            },
+        }))
+        .unwrap();
+        let passives: PassivesResponse = serde_json::from_value(serde_json::json!({
+            "hashes": [1, 2, 3],
+            "hashes_ex": [4],
+            "items": [item("Cobalt Jewel", "PassiveJewels")],
+            "jewel_data": [],
+        }))
+        .unwrap();
+
+        let character = Character::from_legacy(items, passives);
+        assert_eq!("SteelDD", character.name);
+        assert_eq!("Necromancer", character.class);
+        assert_eq!(Some("Standard"), character.league.as_deref());
+        assert_eq!(100, character.level);
+        assert!(character.current);
+
+        let type_lines =
+            |items: &[Item]| -> Vec<String> { items.iter().map(|i| i.type_line.clone()).collect() };
+        assert_eq!(
+            vec!["Vaal Regalia", "Onyx Amulet"],
+            type_lines(&character.equipment)
+        );
+        assert_eq!(vec!["Portal Scroll"], type_lines(&character.inventory));
+        assert_eq!(vec!["Cobalt Jewel"], type_lines(&character.jewels));
+
+        let passives = character.passives.unwrap();
+        assert_eq!(vec![1, 2, 3], passives.hashes);
+        assert_eq!(vec![4], passives.hashes_ex);
+    }
 }
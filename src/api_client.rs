@@ -1,15 +1,63 @@
+use chrono::{DateTime, Utc};
+use futures::{future, stream, Stream, StreamExt};
+use std::convert::TryFrom;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::api::*;
-use crate::client::PoeClient;
-use crate::response::PoeResult;
+use crate::auth::SessionId;
+use crate::client::{Domain, PoeClient};
+use crate::oauth::{OAuthClient, Scope, TokenSet, TokenStore};
+use crate::page::{cursor_stream, CursorPage, PagedStream};
+use crate::query::{LadderQuery, LeaguesQuery, PvpMatchesQuery};
+use crate::response::{PoeError, PoeResult};
+use crate::trade::{
+    ItemCatalog, StatCatalog, StaticCatalog, TradeFetchResponse, TradeLeague, TradeLeaguesResponse,
+    TradeListing, TradeQuery, TradeSearchResponse,
+};
+#[cfg(feature = "reqwest-transport")]
+use crate::transport::ReqwestTransport;
+use crate::transport::Transport;
+#[cfg(feature = "reqwest-transport")]
+use reqwest::Proxy;
+use url::Url;
 
-const WEB_DOMAIN: &str = "https://www.pathofexile.com";
+#[cfg(feature = "reqwest-transport")]
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Maximum number of entries per ladder page.
+const LADDER_PAGE_LIMIT: usize = 200;
+
+/// Number of ladder pages requested concurrently by the ladder streams.
+const LADDER_PAGE_PARALLELISM: usize = 2;
+
+/// Maximum number of listings which can be fetched with a single trade request.
+const TRADE_FETCH_LIMIT: usize = 10;
+
+/// Delay before polling the public stash tab river again once it is caught up.
+const PUBLIC_STASH_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Delay before polling the currency exchange again once it is caught up,
+/// new markets are only published once per hour.
+const CURRENCY_EXCHANGE_BACKOFF: Duration = Duration::from_secs(5 * 60);
 
 /// A builder to construct a configured [`PathOfExile`] client.
 pub struct PathOfExileBuilder {
     application: (String, String),
     contact: Option<String>,
+    max_retries: Option<u32>,
+    token: Option<TokenSet>,
+    session_id: Option<SessionId>,
+    client_credentials: Option<(String, String, Vec<Scope>)>,
+    refresh_tokens: Option<(OAuthClient, Box<dyn TokenStore>)>,
+    api_url: Option<Url>,
+    web_url: Option<Url>,
+    realm: Realm,
+    transport: Option<Arc<dyn Transport>>,
+    #[cfg(feature = "reqwest-transport")]
+    http_client: Option<reqwest::Client>,
+    #[cfg(feature = "reqwest-transport")]
+    http_client_builder: reqwest::ClientBuilder,
 }
 
 impl PathOfExileBuilder {
@@ -17,6 +65,19 @@ impl PathOfExileBuilder {
         Self {
             application: ("poe-rs".to_string(), env!("CARGO_PKG_VERSION").to_string()),
             contact: None,
+            max_retries: None,
+            token: None,
+            session_id: None,
+            client_credentials: None,
+            refresh_tokens: None,
+            api_url: None,
+            web_url: None,
+            realm: Realm::default(),
+            transport: None,
+            #[cfg(feature = "reqwest-transport")]
+            http_client: None,
+            #[cfg(feature = "reqwest-transport")]
+            http_client_builder: reqwest::Client::builder().timeout(DEFAULT_TIMEOUT),
         }
     }
 
@@ -36,9 +97,174 @@ impl PathOfExileBuilder {
         self
     }
 
+    /// Sets the base URL for API requests. Defaults to `https://api.pathofexile.com`.
+    ///
+    /// Useful to route requests through a caching proxy or to test against a local server.
+    pub fn api_url(mut self, url: Url) -> Self {
+        self.api_url = Some(url);
+        self
+    }
+
+    /// Sets the base URL for requests to the website (e.g. the character window and OAuth).
+    /// Defaults to `https://www.pathofexile.com`.
+    pub fn web_url(mut self, url: Url) -> Self {
+        self.web_url = Some(url);
+        self
+    }
+
+    /// Sets the realm used by all requests which do not specify a realm. Defaults to `pc`.
+    ///
+    /// See [`PathOfExile::with_realm`] to use a different realm for some requests.
+    pub fn realm(mut self, realm: Realm) -> Self {
+        self.realm = realm;
+        self
+    }
+
+    /// Sets the timeout for the entire request, from connecting until the response
+    /// has been read completely. Defaults to 30 seconds.
+    ///
+    /// reqwest 0.11 has no separate read timeout, so this is also the only bound on
+    /// reading a slow response.
+    #[cfg(feature = "reqwest-transport")]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.http_client_builder = self.http_client_builder.timeout(timeout);
+        self
+    }
+
+    /// Sets the timeout for establishing a connection.
+    #[cfg(feature = "reqwest-transport")]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.http_client_builder = self.http_client_builder.connect_timeout(timeout);
+        self
+    }
+
+    /// Routes requests through a proxy, can be called multiple times to add multiple proxies.
+    #[cfg(feature = "reqwest-transport")]
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.http_client_builder = self.http_client_builder.proxy(proxy);
+        self
+    }
+
+    /// Adds a trusted root certificate, e.g. for a TLS intercepting proxy.
+    #[cfg(any(
+        feature = "native-tls",
+        feature = "native-tls-vendored",
+        feature = "rustls-tls"
+    ))]
+    pub fn add_root_certificate(mut self, certificate: reqwest::Certificate) -> Self {
+        self.http_client_builder = self.http_client_builder.add_root_certificate(certificate);
+        self
+    }
+
+    /// Enables or disables gzip compressed responses. Enabled by default with the `gzip` feature.
+    #[cfg(feature = "gzip")]
+    pub fn gzip(mut self, enable: bool) -> Self {
+        self.http_client_builder = self.http_client_builder.gzip(enable);
+        self
+    }
+
+    /// Enables or disables brotli compressed responses. Enabled by default with the `brotli` feature.
+    #[cfg(feature = "brotli")]
+    pub fn brotli(mut self, enable: bool) -> Self {
+        self.http_client_builder = self.http_client_builder.brotli(enable);
+        self
+    }
+
+    /// Uses a pre-built [`reqwest::Client`] to send requests.
+    ///
+    /// All other HTTP client options (timeouts, proxies, certificates, compression) of the
+    /// builder are ignored.
+    #[cfg(feature = "reqwest-transport")]
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Sets the transport used to send requests. Defaults to a `ReqwestTransport`
+    /// with the `reqwest-transport` feature, without it a transport is required.
+    ///
+    /// Tokens requested through [`PathOfExileBuilder::client_credentials`] and refreshed through
+    /// [`PathOfExileBuilder::refresh_tokens`] use the same transport.
+    /// All HTTP client options of the builder are ignored when a transport is set.
+    pub fn transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Sets how often a request is retried after being rate limited. Defaults to `3`.
+    ///
+    /// Once all retries are used up the request fails with [`crate::PoeError::RateLimited`].
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
+
+    /// Sets the OAuth token used to authorize requests.
+    ///
+    /// See [`crate::oauth`] on how to obtain a token.
+    pub fn token(mut self, token: TokenSet) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    /// Sets the `POESESSID` cookie of a logged in website session.
+    ///
+    /// The legacy character window endpoints like [`PathOfExile::get_items`] can then read
+    /// the private profile of the account the session belongs to. The cookie is only sent to
+    /// the website and never included in `Debug` output.
+    pub fn session_id(mut self, session_id: impl Into<SessionId>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    /// Refreshes expired access tokens with their refresh token and persists
+    /// the renewed tokens in the `store`.
+    ///
+    /// The token set with [`PathOfExileBuilder::token`] is used as the initial token,
+    /// without it the token is loaded from the `store`.
+    ///
+    /// Tokens are refreshed at the token endpoint of the [`PathOfExileBuilder::web_url`],
+    /// with the transport and User-Agent of this client. These settings of `oauth` are replaced.
+    pub fn refresh_tokens(mut self, oauth: OAuthClient, store: impl TokenStore + 'static) -> Self {
+        self.refresh_tokens = Some((oauth, Box::new(store)));
+        self
+    }
+
+    /// Authorizes requests with tokens obtained through the client credentials grant.
+    ///
+    /// This is required for the `service:*` scopes, which are only available to confidential
+    /// clients. Tokens are requested on demand, cached and renewed once they expire
+    /// or are rejected by the API.
+    pub fn client_credentials(
+        mut self,
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+        scopes: impl IntoIterator<Item = Scope>,
+    ) -> Self {
+        self.client_credentials = Some((
+            client_id.into(),
+            client_secret.into(),
+            scopes.into_iter().collect(),
+        ));
+        self
+    }
+
     /// Builds a [`PathOfExile`] which can be used to make API requests.
-    pub fn build(self) -> PathOfExile {
-        let mut client = PoeClient::new();
+    ///
+    /// Fails if the HTTP client can not be created with the configured options,
+    /// or with [`PoeError::MissingTransport`] if no transport is set without the
+    /// `reqwest-transport` feature.
+    pub fn build(self) -> PoeResult<PathOfExile> {
+        #[cfg(feature = "reqwest-transport")]
+        let transport: Arc<dyn Transport> = match (self.transport, self.http_client) {
+            (Some(transport), _) => transport,
+            (None, Some(http_client)) => Arc::new(ReqwestTransport::new(http_client)),
+            (None, None) => Arc::new(ReqwestTransport::new(self.http_client_builder.build()?)),
+        };
+        #[cfg(not(feature = "reqwest-transport"))]
+        let transport = self.transport.ok_or(PoeError::MissingTransport)?;
+
+        let mut client = PoeClient::new(transport);
 
         let mut user_agent = format!("{}/{}", self.application.0, self.application.1);
         if let Some(contact) = self.contact.as_ref() {
@@ -46,9 +272,42 @@ impl PathOfExileBuilder {
             user_agent.push_str(contact);
             user_agent.push(')');
         }
-        client.user_agent(user_agent);
+        client.user_agent(user_agent.clone());
 
-        client.into()
+        if let Some(api_url) = self.api_url {
+            client.api_url(api_url);
+        }
+        if let Some(web_url) = self.web_url {
+            client.web_url(web_url);
+        }
+        if let Some(max_retries) = self.max_retries {
+            client.max_retries(max_retries);
+        }
+        match (self.refresh_tokens, self.token) {
+            (Some((oauth, store)), token) => {
+                let oauth = oauth
+                    .token_url(client.url(Domain::Web, "/oauth/token"))
+                    .transport(client.shared_transport())
+                    .user_agent(user_agent.clone());
+                client.refresh_tokens(oauth, store, token);
+            }
+            (None, Some(token)) => client.token(token),
+            (None, None) => {}
+        }
+        if let Some(session_id) = self.session_id {
+            client.session_id(session_id);
+        }
+        if let Some((client_id, client_secret, scopes)) = self.client_credentials {
+            let oauth = OAuthClient::confidential(client_id, client_secret)
+                .token_url(client.url(Domain::Web, "/oauth/token"))
+                .transport(client.shared_transport())
+                .user_agent(user_agent);
+            client.client_credentials(oauth, scopes);
+        }
+
+        let mut poe = PathOfExile::from(client);
+        poe.realm = self.realm;
+        Ok(poe)
     }
 }
 
@@ -62,8 +321,10 @@ impl PathOfExileBuilder {
 #[derive(Clone)]
 pub struct PathOfExile {
     client: Arc<PoeClient>,
+    realm: Realm,
 }
 
+#[cfg(feature = "reqwest-transport")]
 impl Default for PathOfExile {
     fn default() -> Self {
         Self::new()
@@ -74,73 +335,188 @@ impl From<PoeClient> for PathOfExile {
     fn from(client: PoeClient) -> Self {
         Self {
             client: Arc::new(client),
+            realm: Realm::default(),
         }
     }
 }
 
 impl PathOfExile {
+    #[cfg(feature = "reqwest-transport")]
     pub fn new() -> Self {
-        Self::builder().build()
+        Self::builder()
+            .build()
+            .expect("the default configuration is always valid")
     }
 
     pub fn builder() -> PathOfExileBuilder {
         PathOfExileBuilder::new()
     }
 
+    /// The realm used by requests which do not specify a realm.
+    pub fn realm(&self) -> &Realm {
+        &self.realm
+    }
+
+    /// Returns a client which makes requests for another realm.
+    ///
+    /// The client shares the connection pool, rate limits and tokens with this client.
+    pub fn with_realm(&self, realm: Realm) -> Self {
+        Self {
+            client: self.client.clone(),
+            realm,
+        }
+    }
+
+    /// Path segment of the realm, which is omitted for `pc`.
+    fn realm_path(&self) -> String {
+        match &self.realm {
+            Realm::Pc => String::new(),
+            realm => format!("/{}", realm.as_str()),
+        }
+    }
+
+    /// Uses the legacy character window, which only works for public profiles
+    /// or with the [`PathOfExileBuilder::session_id`] of the owner.
+    /// Prefer [`PathOfExile::characters`] with an OAuth token.
     pub async fn get_characters(
         &self,
         account_name: impl AsRef<str>,
     ) -> PoeResult<Vec<CharacterInfo>> {
-        let url = &format!(
-            "{}/character-window/get-characters?accountName={}",
-            WEB_DOMAIN,
-            account_name.as_ref()
-        );
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("accountName", account_name.as_ref())
+            .append_pair("realm", self.realm.as_str())
+            .finish();
+        let url = &format!("/character-window/get-characters?{query}");
 
-        self.client.get("get_characters", url).await
+        self.client.get("get_characters", Domain::Web, url).await
     }
 
+    /// Uses the legacy character window, which only works for public profiles
+    /// or with the [`PathOfExileBuilder::session_id`] of the owner.
+    /// Prefer [`PathOfExile::character`] with an OAuth token.
     pub async fn get_items(
         &self,
         account_name: impl AsRef<str>,
         character: impl AsRef<str>,
     ) -> PoeResult<ItemsResponse> {
-        let url = &format!(
-            "{}/character-window/get-items?accountName={}&character={}",
-            WEB_DOMAIN,
-            account_name.as_ref(),
-            character.as_ref()
-        );
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("accountName", account_name.as_ref())
+            .append_pair("character", character.as_ref())
+            .append_pair("realm", self.realm.as_str())
+            .finish();
+        let url = &format!("/character-window/get-items?{query}");
 
-        self.client.get("get_items", url).await
+        self.client.get("get_items", Domain::Web, url).await
     }
 
+    /// Uses the legacy character window, which only works for public profiles
+    /// or with the [`PathOfExileBuilder::session_id`] of the owner.
+    /// Prefer [`PathOfExile::character`] with an OAuth token.
     pub async fn get_passives(
         &self,
         account_name: impl AsRef<str>,
         character: impl AsRef<str>,
         skill_tree_data: bool,
     ) -> PoeResult<PassivesResponse> {
-        let url = &format!(
-            "{}/character-window/get-passive-skills?accountName={}&character={}&reqData={}",
-            WEB_DOMAIN,
-            account_name.as_ref(),
-            character.as_ref(),
-            i32::from(skill_tree_data)
-        );
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("accountName", account_name.as_ref())
+            .append_pair("character", character.as_ref())
+            .append_pair("reqData", &i32::from(skill_tree_data).to_string())
+            .append_pair("realm", self.realm.as_str())
+            .finish();
+        let url = &format!("/character-window/get-passive-skills?{query}");
 
-        self.client.get("get_passives", url).await
+        self.client.get("get_passives", Domain::Web, url).await
     }
 
-    pub async fn leagues(&self, limit: usize, offset: usize) -> PoeResult<Vec<League>> {
-        self.client
+    /// Returns the items of a stash tab through the legacy character window,
+    /// with `tabs` the list of all stash tabs is included.
+    ///
+    /// Requires the [`PathOfExileBuilder::session_id`] of the owner, unless the tab is public.
+    /// Prefer [`PathOfExile::stash`] with an OAuth token.
+    pub async fn get_stash_items(
+        &self,
+        account_name: impl AsRef<str>,
+        league: impl AsRef<str>,
+        tab_index: u32,
+        tabs: bool,
+    ) -> PoeResult<StashItemsResponse> {
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("accountName", account_name.as_ref())
+            .append_pair("league", league.as_ref())
+            .append_pair("tabIndex", &tab_index.to_string())
+            .append_pair("tabs", &i32::from(tabs).to_string())
+            .append_pair("realm", self.realm.as_str())
+            .finish();
+        let url = &format!("/character-window/get-stash-items?{query}");
+
+        self.client.get("get_stash_items", Domain::Web, url).await
+    }
+
+    /// Returns the profile of the account.
+    ///
+    /// Requires a token with the `account:profile` scope.
+    pub async fn profile(&self) -> PoeResult<Profile> {
+        self.client.get("profile", Domain::Api, "/profile").await
+    }
+
+    /// Returns the leagues the account can participate in, including private leagues.
+    ///
+    /// Requires a token with the `account:leagues` scope.
+    pub async fn account_leagues(&self) -> PoeResult<Vec<League>> {
+        let response: LeaguesResponse = self
+            .client
+            .get(
+                "account_leagues",
+                Domain::Api,
+                &format!("/account/leagues{}", self.realm_path()),
+            )
+            .await?;
+        Ok(response.leagues)
+    }
+
+    /// Returns all characters of the account, without equipment and passives.
+    ///
+    /// Requires a token with the `account:characters` scope.
+    pub async fn characters(&self) -> PoeResult<Vec<Character>> {
+        let response: CharactersResponse = self
+            .client
             .get(
-                "leagues",
-                &format!("/leagues?limit={limit}&offset={offset}"),
+                "characters",
+                Domain::Api,
+                &format!("/character{}", self.realm_path()),
             )
+            .await?;
+        Ok(response.characters)
+    }
+
+    /// Returns a character of the account, including equipment, inventory, jewels and passives.
+    ///
+    /// Requires a token with the `account:characters` scope.
+    pub async fn character(&self, name: impl AsRef<str>) -> PoeResult<Character> {
+        let url = format!("/character{}/{}", self.realm_path(), name.as_ref());
+        let response: CharacterResponse = self.client.get("character", Domain::Api, &url).await?;
+        Ok(response.character)
+    }
+
+    pub async fn leagues(&self, limit: usize, offset: usize) -> PoeResult<Vec<League>> {
+        self.leagues_with(&LeaguesQuery::new().limit(limit).offset(offset))
             .await
     }
 
+    /// Lists leagues filtered by realm, type and season.
+    pub async fn leagues_with(&self, query: &LeaguesQuery) -> PoeResult<Vec<League>> {
+        let url = format!("/leagues?{}", query.to_query_string(&self.realm));
+        self.client.get("leagues", Domain::Api, &url).await
+    }
+
+    /// Returns a single league.
+    pub async fn league(&self, id: impl AsRef<str>) -> PoeResult<League> {
+        let url = format!("/league/{}?realm={}", id.as_ref(), self.realm.as_str());
+        let response: LeagueResponse = self.client.get("league", Domain::Api, &url).await?;
+        Ok(response.league)
+    }
+
     pub async fn ladder(
         &self,
         name: impl AsRef<str>,
@@ -150,21 +526,1397 @@ impl PathOfExile {
         self.client
             .get(
                 "ladder",
+                Domain::Api,
                 &format!(
-                    "/ladders/{}?limit={}&offset={}",
+                    "/ladders/{}?limit={}&offset={}&realm={}",
                     name.as_ref(),
                     limit,
-                    offset
+                    offset,
+                    self.realm.as_str()
                 ),
             )
             .await
     }
+
+    /// Returns the ladder of a league with sort order, class filter and realm.
+    ///
+    /// Requires a token with the `service:leagues:ladder` scope.
+    pub async fn league_ladder(
+        &self,
+        league: impl AsRef<str>,
+        query: &LadderQuery,
+    ) -> PoeResult<LeagueLadderResponse> {
+        let url = format!(
+            "/league/{}/ladder?{}",
+            league.as_ref(),
+            query.to_query_string(&self.realm)
+        );
+        self.client.get("league_ladder", Domain::Api, &url).await
+    }
+
+    /// Returns the event ladder of a league, ranking private leagues.
+    ///
+    /// Requires a token with the `service:leagues:ladder` scope.
+    pub async fn event_ladder(
+        &self,
+        league: impl AsRef<str>,
+        limit: usize,
+        offset: usize,
+    ) -> PoeResult<EventLadderResponse> {
+        let url = format!(
+            "/league/{}/event-ladder?limit={}&offset={}&realm={}",
+            league.as_ref(),
+            limit,
+            offset,
+            self.realm.as_str()
+        );
+        self.client.get("event_ladder", Domain::Api, &url).await
+    }
+
+    /// Streams all entries of the event ladder of a league.
+    pub fn event_ladder_stream(
+        &self,
+        league: impl Into<String>,
+    ) -> impl Stream<Item = PoeResult<EventLadderEntry>> {
+        let poe = self.clone();
+        let league = league.into();
+        PagedStream::new(
+            LADDER_PAGE_PARALLELISM,
+            LADDER_PAGE_LIMIT,
+            None,
+            move |page| {
+                let poe = poe.clone();
+                let league = league.clone();
+                async move {
+                    let response = poe.event_ladder(league, page.limit, page.offset).await?;
+                    Ok(response.ladder.entries.into_iter())
+                }
+            },
+        )
+    }
+
+    /// Lists PvP matches, by default the upcoming ones.
+    ///
+    /// Requires a token with the `service:pvp_matches` scope.
+    pub async fn pvp_matches(&self, query: &PvpMatchesQuery) -> PoeResult<Vec<PvpMatch>> {
+        let url = format!("/pvp-match?{}", query.to_query_string(&self.realm));
+        let response: PvpMatchesResponse =
+            self.client.get("pvp_matches", Domain::Api, &url).await?;
+        Ok(response.matches)
+    }
+
+    /// Returns a single PvP match.
+    ///
+    /// Requires a token with the `service:pvp_matches` scope.
+    pub async fn pvp_match(&self, id: impl AsRef<str>) -> PoeResult<PvpMatch> {
+        let url = format!("/pvp-match/{}?realm={}", id.as_ref(), self.realm.as_str());
+        let response: PvpMatchResponse = self.client.get("pvp_match", Domain::Api, &url).await?;
+        Ok(response.pvp_match)
+    }
+
+    /// Returns the ladder of a PvP match.
+    ///
+    /// Requires a token with the `service:pvp_matches:ladder` scope.
+    pub async fn pvp_match_ladder(
+        &self,
+        id: impl AsRef<str>,
+        limit: usize,
+        offset: usize,
+    ) -> PoeResult<PvpLadderResponse> {
+        let url = format!(
+            "/pvp-match/{}/ladder?limit={}&offset={}&realm={}",
+            id.as_ref(),
+            limit,
+            offset,
+            self.realm.as_str()
+        );
+        self.client.get("pvp_match_ladder", Domain::Api, &url).await
+    }
+
+    /// Streams all teams of the ladder of a PvP match.
+    pub fn pvp_match_ladder_stream(
+        &self,
+        id: impl Into<String>,
+    ) -> impl Stream<Item = PoeResult<PvpLadderTeamEntry>> {
+        let poe = self.clone();
+        let id = id.into();
+        PagedStream::new(
+            LADDER_PAGE_PARALLELISM,
+            LADDER_PAGE_LIMIT,
+            None,
+            move |page| {
+                let poe = poe.clone();
+                let id = id.clone();
+                async move {
+                    let response = poe.pvp_match_ladder(id, page.limit, page.offset).await?;
+                    Ok(response.ladder.entries.into_iter())
+                }
+            },
+        )
+    }
+
+    /// Returns all stash tabs of the account in a league, without their items.
+    ///
+    /// Requires a token with the `account:stashes` scope.
+    pub async fn stashes(&self, league: impl AsRef<str>) -> PoeResult<Vec<StashTab>> {
+        let url = format!("/stash{}/{}", self.realm_path(), league.as_ref());
+        let response: StashesResponse = self.client.get("stashes", Domain::Api, &url).await?;
+        Ok(response.stashes)
+    }
+
+    /// Returns a stash tab with its items, or a substash of a special tab like the map stash.
+    ///
+    /// Requires a token with the `account:stashes` scope.
+    pub async fn stash(
+        &self,
+        league: impl AsRef<str>,
+        stash_id: impl AsRef<str>,
+        substash_id: Option<&str>,
+    ) -> PoeResult<StashTab> {
+        let base = format!("/stash{}/{}", self.realm_path(), league.as_ref());
+        let url = stash_path(&base, stash_id.as_ref(), substash_id);
+        let response: StashResponse = self.client.get("stash", Domain::Api, &url).await?;
+        Ok(response.stash)
+    }
+
+    /// Returns all stash tabs of the guild of the account in a league, without their items.
+    ///
+    /// Requires a token with the `account:guild:stashes` scope.
+    pub async fn guild_stashes(&self, league: impl AsRef<str>) -> PoeResult<Vec<StashTab>> {
+        let url = format!("/guild{}/{}/stash", self.realm_path(), league.as_ref());
+        let response: StashesResponse = self.client.get("guild_stashes", Domain::Api, &url).await?;
+        Ok(response.stashes)
+    }
+
+    /// Returns a guild stash tab with its items, or a substash of a special tab.
+    ///
+    /// Requires a token with the `account:guild:stashes` scope.
+    pub async fn guild_stash(
+        &self,
+        league: impl AsRef<str>,
+        stash_id: impl AsRef<str>,
+        substash_id: Option<&str>,
+    ) -> PoeResult<StashTab> {
+        let base = format!("/guild{}/{}/stash", self.realm_path(), league.as_ref());
+        let url = stash_path(&base, stash_id.as_ref(), substash_id);
+        let response: StashResponse = self.client.get("guild_stash", Domain::Api, &url).await?;
+        Ok(response.stash)
+    }
+
+    /// Lists the item filters of the account, without the filters themselves.
+    ///
+    /// Requires a token with the `account:item_filter` scope.
+    pub async fn item_filters(&self) -> PoeResult<Vec<ItemFilter>> {
+        let response: ItemFiltersResponse = self
+            .client
+            .get("item_filters", Domain::Api, "/item-filter")
+            .await?;
+        Ok(response.filters)
+    }
+
+    /// Returns an item filter of the account.
+    ///
+    /// Requires a token with the `account:item_filter` scope.
+    pub async fn item_filter(&self, id: impl AsRef<str>) -> PoeResult<ItemFilter> {
+        let url = format!("/item-filter/{}", id.as_ref());
+        let response: ItemFilterResponse =
+            self.client.get("item_filter", Domain::Api, &url).await?;
+        Ok(response.filter)
+    }
+
+    /// Creates a new item filter.
+    ///
+    /// With `validate` the filter is validated against the current game version,
+    /// an invalid filter is still created and fails with [`PoeError::InvalidItemFilter`],
+    /// which carries the created filter.
+    ///
+    /// Requires a token with the `account:item_filter` scope.
+    pub async fn create_item_filter(
+        &self,
+        filter: &ItemFilter,
+        validate: bool,
+    ) -> PoeResult<ItemFilter> {
+        let url = format!("/item-filter?validate={validate}");
+        let response: ItemFilterResponse = self
+            .client
+            .post("create_item_filter", Domain::Api, &url, filter)
+            .await?;
+        validated(response.filter)
+    }
+
+    /// Updates an existing item filter.
+    ///
+    /// With `validate` the filter is validated against the current game version,
+    /// an invalid filter is still updated and fails with [`PoeError::InvalidItemFilter`],
+    /// which carries the updated filter.
+    ///
+    /// Requires a token with the `account:item_filter` scope.
+    pub async fn update_item_filter(
+        &self,
+        id: impl AsRef<str>,
+        filter: &ItemFilter,
+        validate: bool,
+    ) -> PoeResult<ItemFilter> {
+        let url = format!("/item-filter/{}?validate={}", id.as_ref(), validate);
+        let response: ItemFilterResponse = self
+            .client
+            .post("update_item_filter", Domain::Api, &url, filter)
+            .await?;
+        validated(response.filter)
+    }
+
+    /// Returns a batch of public stash tab changes, starting with the oldest changes
+    /// without a `next_change_id`.
+    ///
+    /// Requires a token with the `service:psapi` scope.
+    pub async fn public_stashes(
+        &self,
+        next_change_id: Option<&str>,
+    ) -> PoeResult<PublicStashesResponse> {
+        let mut url = format!("/public-stash-tabs{}", self.realm_path());
+        if let Some(id) = next_change_id {
+            url.push_str(&format!("?id={id}"));
+        }
+
+        self.client.get("public_stashes", Domain::Api, &url).await
+    }
+
+    /// Follows the public stash tab river forever, starting at `next_change_id`.
+    ///
+    /// Once the river is caught up, new changes are polled with a delay.
+    /// The stream ends after the first error and can be resumed with the
+    /// `next_change_id` of the last received batch.
+    pub fn public_stash_stream(
+        &self,
+        next_change_id: Option<String>,
+    ) -> impl Stream<Item = PoeResult<PublicStashesResponse>> {
+        let poe = self.clone();
+        cursor_stream(next_change_id, PUBLIC_STASH_BACKOFF, move |id| {
+            let poe = poe.clone();
+            async move {
+                let response = poe.public_stashes(id.as_deref()).await?;
+                let caught_up = response.stashes.is_empty()
+                    || id.as_deref() == Some(response.next_change_id.as_str());
+                Ok(CursorPage {
+                    next: Some(response.next_change_id.clone()),
+                    value: response,
+                    caught_up,
+                })
+            }
+        })
+    }
+
+    /// Returns the currency exchange markets of an hour, by default the oldest available hour.
+    ///
+    /// `id` is the unix timestamp of the hour, e.g. the `next_change_id` of a previous response.
+    ///
+    /// Requires a token with the `service:cxapi` scope.
+    pub async fn currency_exchange(&self, id: Option<u64>) -> PoeResult<CurrencyExchangeResponse> {
+        let mut url = format!("/currency-exchange{}", self.realm_path());
+        if let Some(id) = id {
+            url.push_str(&format!("/{id}"));
+        }
+
+        self.client
+            .get("currency_exchange", Domain::Api, &url)
+            .await
+    }
+
+    /// Replays the currency exchange history hour by hour, starting at `from`,
+    /// and keeps polling for new hours once it is caught up.
+    ///
+    /// The stream ends after the first error and can be resumed with the
+    /// `next_change_id` of the last received response.
+    pub fn currency_exchange_stream(
+        &self,
+        from: DateTime<Utc>,
+    ) -> impl Stream<Item = PoeResult<CurrencyExchangeResponse>> {
+        let poe = self.clone();
+        let start = u64::try_from(from.timestamp()).unwrap_or_default();
+        cursor_stream(start, CURRENCY_EXCHANGE_BACKOFF, move |id| {
+            let poe = poe.clone();
+            async move {
+                let response = poe.currency_exchange(Some(id)).await?;
+                Ok(CursorPage {
+                    next: response.next_change_id,
+                    caught_up: response.next_change_id <= id,
+                    value: response,
+                })
+            }
+        })
+    }
+
+    /// Searches the trade site, returns the ids of at most 100 matching listings.
+    pub async fn trade_search(
+        &self,
+        league: impl AsRef<str>,
+        query: &TradeQuery,
+    ) -> PoeResult<TradeSearchResponse> {
+        let url = format!("/api/trade/search{}/{}", self.realm_path(), league.as_ref());
+        self.client
+            .post("trade_search", Domain::Web, &url, query)
+            .await
+    }
+
+    /// Fetches listings of a search, at most 10 at a time.
+    ///
+    /// Listings which are no longer available are skipped.
+    pub async fn trade_fetch(
+        &self,
+        ids: &[String],
+        search_id: impl AsRef<str>,
+    ) -> PoeResult<Vec<TradeListing>> {
+        let url = format!(
+            "/api/trade/fetch/{}?query={}",
+            ids.join(","),
+            search_id.as_ref()
+        );
+        let response: TradeFetchResponse =
+            self.client.get("trade_fetch", Domain::Web, &url).await?;
+        Ok(response.result.into_iter().flatten().collect())
+    }
+
+    /// Fetches all listings of a search in batches of 10, one batch after another
+    /// to stay within the rate limits of the trade site.
+    ///
+    /// The stream ends after the first error.
+    pub fn trade_fetch_stream(
+        &self,
+        search: &TradeSearchResponse,
+    ) -> impl Stream<Item = PoeResult<TradeListing>> {
+        // a batch can be empty if all of its listings are gone, which would end a `PagedStream`
+        let batches: Vec<Vec<String>> = search
+            .result
+            .chunks(TRADE_FETCH_LIMIT)
+            .map(<[String]>::to_vec)
+            .collect();
+        let state = (self.clone(), search.id.clone(), batches.into_iter());
+
+        stream::unfold(Some(state), |state| async move {
+            let (poe, search_id, mut batches) = state?;
+            let ids = batches.next()?;
+
+            match poe.trade_fetch(&ids, &search_id).await {
+                Ok(listings) => {
+                    let listings = stream::iter(listings.into_iter().map(Ok));
+                    Some((listings.left_stream(), Some((poe, search_id, batches))))
+                }
+                Err(err) => Some((stream::once(future::ready(Err(err))).right_stream(), None)),
+            }
+        })
+        .flatten()
+    }
+
+    /// Returns the item names and base types which can be searched on the trade site.
+    pub async fn trade_items(&self) -> PoeResult<ItemCatalog> {
+        self.client
+            .get("trade_data", Domain::Web, "/api/trade/data/items")
+            .await
+    }
+
+    /// Returns the stats which can be searched on the trade site.
+    pub async fn trade_stats(&self) -> PoeResult<StatCatalog> {
+        self.client
+            .get("trade_data", Domain::Web, "/api/trade/data/stats")
+            .await
+    }
+
+    /// Returns the currencies and other exchangeable items of the trade site.
+    pub async fn trade_static(&self) -> PoeResult<StaticCatalog> {
+        self.client
+            .get("trade_data", Domain::Web, "/api/trade/data/static")
+            .await
+    }
+
+    /// Returns the leagues which can be searched on the trade site.
+    pub async fn trade_leagues(&self) -> PoeResult<Vec<TradeLeague>> {
+        let response: TradeLeaguesResponse = self
+            .client
+            .get("trade_data", Domain::Web, "/api/trade/data/leagues")
+            .await?;
+        Ok(response.result)
+    }
+}
+
+fn stash_path(base: &str, stash_id: &str, substash_id: Option<&str>) -> String {
+    match substash_id {
+        Some(substash_id) => format!("{base}/{stash_id}/{substash_id}"),
+        None => format!("{base}/{stash_id}"),
+    }
+}
+
+fn validated(mut filter: ItemFilter) -> PoeResult<ItemFilter> {
+    match filter.validation.take() {
+        Some(validation) if !validation.valid => Err(PoeError::InvalidItemFilter {
+            validation,
+            filter: Box::new(filter),
+        }),
+        validation => Ok(ItemFilter {
+            validation,
+            ..filter
+        }),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::PathOfExile;
+    #[cfg(feature = "reqwest-transport")]
+    use super::PathOfExileBuilder;
+    use crate::api::Realm;
+    #[cfg(feature = "reqwest-transport")]
+    use crate::api::{ItemFilter, LadderSort};
+    use crate::oauth::{InMemoryTokenStore, OAuthClient, TokenSet};
+    use crate::query::LadderQuery;
+    #[cfg(feature = "reqwest-transport")]
+    use crate::response::PoeError;
+    #[cfg(feature = "reqwest-transport")]
+    use crate::trade::TradeQuery;
+    use crate::transport::{InMemoryTransport, Response};
+    use futures::StreamExt;
+    use std::sync::Arc;
+    #[cfg(feature = "reqwest-transport")]
+    use std::time::Duration;
+    use url::Url;
+    #[cfg(feature = "reqwest-transport")]
+    use wiremock::matchers::{body_partial_json, header, method, path, query_param};
+    #[cfg(feature = "reqwest-transport")]
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// Creates a client which sends API and website requests to the mock server.
+    #[cfg(feature = "reqwest-transport")]
+    fn builder(server: &MockServer) -> PathOfExileBuilder {
+        let url = Url::parse(&server.uri()).unwrap();
+        PathOfExile::builder().api_url(url.clone()).web_url(url)
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    fn poe(server: &MockServer) -> PathOfExile {
+        builder(server).build().unwrap()
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn local_api_url() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/ladders/Standard"))
+            .and(query_param("limit", "1"))
+            .and(query_param("offset", "0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "total": 15000,
+                "cached_since": "2021-01-01T00:00:00Z",
+                "entries": [{
+                    "rank": 1,
+                    "dead": false,
+                    "character": {
+                        "id": "abc",
+                        "name": "SteelDD",
+                        "level": 100,
+                        "class": "Ascendant",
+                        "experience": 4250334444u64,
+                    },
+                    "account": { "name": "Steelmage", "realm": "pc" },
+                }],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let poe = poe(&server);
+
+        let ladder = poe.ladder("Standard", 1, 0).await.unwrap();
+        assert_eq!(15000, ladder.total);
+        assert_eq!("SteelDD", ladder.entries[0].character.name);
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let poe = builder(&server)
+            .timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+
+        match poe.leagues(1, 0).await {
+            Err(PoeError::Reqwest(err)) => assert!(err.is_timeout()),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn http_client() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header("X-Custom", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("X-Custom", "1".parse().unwrap());
+        let http_client = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()
+            .unwrap();
+
+        let poe = builder(&server).http_client(http_client).build().unwrap();
+
+        assert!(poe.leagues(1, 0).await.unwrap().is_empty());
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn local_web_url() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/character-window/get-characters"))
+            .and(query_param("accountName", "Steelmage#1234"))
+            .and(query_param("realm", "pc"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!([{
+                    "ascendancyClass": 0,
+                    "class": "Scion",
+                    "classId": 0,
+                    "experience": 0,
+                    "league": "Standard",
+                    "level": 1,
+                    "name": "SteelDD",
+                }])),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let poe = poe(&server);
+
+        let characters = poe.get_characters("Steelmage#1234").await.unwrap();
+        assert_eq!("SteelDD", characters[0].name);
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn profile_and_account_leagues() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/profile"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "uuid": "0f1e2d3c",
+                "name": "Steelmage#1234",
+                "realm": "pc",
+                "guild": { "name": "Exiles", "tag": "EX" },
+                "twitch": { "name": "steelmage" },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/account/leagues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "leagues": [{
+                    "id": "Community Race (PL123)",
+                    "realm": "pc",
+                    "registerAt": "2021-01-01T00:00:00Z",
+                    "startAt": "2021-01-02T00:00:00Z",
+                }],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let poe = poe(&server);
+
+        let profile = poe.profile().await.unwrap();
+        assert_eq!("0f1e2d3c", profile.uuid);
+        assert_eq!("EX", profile.guild.unwrap().tag);
+        assert_eq!("steelmage", profile.twitch.unwrap().name);
+
+        let leagues = poe.account_leagues().await.unwrap();
+        assert_eq!("Community Race (PL123)", leagues[0].id);
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn league() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/league/Hardcore"))
+            .and(query_param("realm", "xbox"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "league": {
+                    "id": "Hardcore",
+                    "realm": "xbox",
+                    "description": "A hardcore league.",
+                    "category": { "id": "Hardcore", "current": true },
+                    "rules": [{ "id": "Hardcore", "name": "Hardcore" }],
+                    "registerAt": "2013-01-23T21:00:00Z",
+                    "url": "https://www.pathofexile.com/forum/view-thread/71276",
+                    "startAt": "2013-01-23T21:00:00Z",
+                    "timedEvent": true,
+                    "delveEvent": true,
+                    "privateLeagueUrl": "https://www.pathofexile.com/private-leagues/league/1",
+                },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let poe = poe(&server);
+
+        let league = poe
+            .with_realm(Realm::Xbox)
+            .league("Hardcore")
+            .await
+            .unwrap();
+        assert_eq!(Realm::Xbox, league.realm);
+        assert!(league.category.unwrap().current);
+        assert_eq!(None, league.rules[0].description);
+        assert!(league.timed_event);
+        assert!(!league.score_event);
+        assert!(league.private_league_url.is_some());
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn league_ladder() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/league/Standard/ladder"))
+            .and(query_param("sort", "depthsolo"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "league": { "id": "Standard", "realm": "pc" },
+                "ladder": {
+                    "total": 1,
+                    "cached_since": "2021-01-01T00:00:00Z",
+                    "entries": [{
+                        "rank": 1,
+                        "retired": true,
+                        "public": true,
+                        "character": {
+                            "id": "abc",
+                            "name": "DelveDD",
+                            "level": 98,
+                            "class": "Assassin",
+                            "experience": 3000000000u64,
+                            "depth": { "default": 1200, "solo": 1100 },
+                        },
+                        "account": {
+                            "name": "Steelmage",
+                            "realm": "pc",
+                            "challenges": { "set": "Standard", "completed": 40, "max": 40 },
+                            "twitch": { "name": "steelmage" },
+                        },
+                    }],
+                },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let poe = poe(&server);
+
+        let query = LadderQuery::new().sort(LadderSort::DepthSolo);
+        let response = poe.league_ladder("Standard", &query).await.unwrap();
+        let entry = &response.ladder.entries[0];
+        assert!(entry.retired);
+        assert!(entry.public);
+        assert!(!entry.dead);
+        assert_eq!(1100, entry.character.depth.as_ref().unwrap().solo);
+        assert_eq!(40, entry.account.challenges.as_ref().unwrap().completed);
+        assert_eq!("steelmage", entry.account.twitch.as_ref().unwrap().name);
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn pvp_match_ladder_stream() {
+        fn team(rank: usize) -> serde_json::Value {
+            serde_json::json!({
+                "rank": rank,
+                "rating": 1500,
+                "members": [{
+                    "account": { "name": format!("account-{rank}"), "realm": "pc" },
+                    "character": { "id": "abc", "name": "Duelist", "level": 90, "class": "Slayer" },
+                    "public": true,
+                }],
+            })
+        }
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/pvp-match/Blitz/ladder"))
+            .and(query_param("offset", "0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "match": { "id": "Blitz", "style": "Blitz", "pvp": true },
+                "ladder": { "total": 250, "entries": (1..=200).map(team).collect::<Vec<_>>() },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/pvp-match/Blitz/ladder"))
+            .and(query_param("offset", "200"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "match": { "id": "Blitz", "style": "Blitz", "pvp": true },
+                "ladder": { "total": 250, "entries": (201..=250).map(team).collect::<Vec<_>>() },
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/pvp-match/Blitz/ladder"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "match": { "id": "Blitz", "style": "Blitz", "pvp": true },
+                "ladder": { "total": 250, "entries": [] },
+            })))
+            .mount(&server)
+            .await;
+
+        let poe = poe(&server);
+
+        let teams: Vec<_> = poe
+            .pvp_match_ladder_stream("Blitz")
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(250, teams.len());
+        assert_eq!(250, teams[249].rank);
+        assert_eq!("account-1", teams[0].members[0].account.name);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn currency_exchange_stream() {
+        let transport = Arc::new(InMemoryTransport::new(|request| {
+            let body = match request.uri().path() {
+                "/currency-exchange/xbox/1700000000" => serde_json::json!({
+                    "next_change_id": 1700003600u64,
+                    "markets": [{
+                        "league": "Standard",
+                        "market_id": "chaos|divine",
+                        "volume_traded": { "chaos": 15000, "divine": 100 },
+                        "lowest_stock": { "chaos": 300, "divine": 2 },
+                        "highest_stock": { "chaos": 90000, "divine": 600 },
+                        "lowest_ratio": { "chaos": 148, "divine": 1 },
+                        "highest_ratio": { "chaos": 152, "divine": 1 },
+                    }],
+                }),
+                _ => serde_json::json!({ "next_change_id": 1700003600u64, "markets": [] }),
+            };
+            Response::new(serde_json::to_vec(&body).unwrap())
+        }));
+
+        let poe = PathOfExile::builder()
+            .transport(transport.clone())
+            .build()
+            .unwrap();
+
+        let from = chrono::DateTime::from_timestamp(1700000000, 0).unwrap();
+        let stream = poe.with_realm(Realm::Xbox).currency_exchange_stream(from);
+        futures::pin_mut!(stream);
+
+        let hour = stream.next().await.unwrap().unwrap();
+        let market = &hour.markets[0];
+        assert_eq!("chaos|divine", market.market_id);
+        assert_eq!(Some(&100), market.volume_traded.get("divine"));
+        assert_eq!(Some(&148), market.lowest_ratio.get("chaos"));
+
+        let start = tokio::time::Instant::now();
+        for _ in 0..2 {
+            let hour = stream.next().await.unwrap().unwrap();
+            assert!(hour.markets.is_empty());
+        }
+        assert_eq!(super::CURRENCY_EXCHANGE_BACKOFF, start.elapsed());
+
+        let requests = transport.requests();
+        assert_eq!("/currency-exchange/xbox/1700003600", requests[2].url.path());
+    }
+
+    fn trade_listing(id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": id,
+            "listing": {
+                "method": "psapi",
+                "indexed": "2021-01-01T00:00:00Z",
+                "stash": { "name": "~price 1 chaos", "x": 0, "y": 0 },
+                "account": { "name": "Steelmage", "lastCharacterName": "SteelDD" },
+                "price": { "type": "~price", "amount": 1.0, "currency": "chaos" },
+            },
+            "item": {
+                "verified": true,
+                "w": 1,
+                "h": 1,
+                "icon": "https://web.poecdn.com/image.png",
+                "name": "",
+                "typeLine": "Orb of Alteration",
+                "baseType": "Orb of Alteration",
+                "identified": true,
+                "ilvl": 0,
+            },
+        })
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn trade_search_and_fetch() {
+        let ids: Vec<String> = (0..15).map(|i| format!("id{i}")).collect();
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/trade/search/Standard"))
+            .and(body_partial_json(serde_json::json!({
+                "query": { "type": "Orb of Alteration" },
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "search",
+                "complexity": 1,
+                "result": ids,
+                "total": 15,
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/api/trade/fetch/{}", ids[..10].join(","))))
+            .and(query_param("query", "search"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "result": ids[..10].iter().map(|id| trade_listing(id)).collect::<Vec<_>>(),
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!("/api/trade/fetch/{}", ids[10..].join(","))))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "result": [
+                    trade_listing("id10"),
+                    null,
+                    trade_listing("id12"),
+                    trade_listing("id13"),
+                    trade_listing("id14"),
+                ],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let poe = poe(&server);
+
+        let query = TradeQuery::new().item_type("Orb of Alteration");
+        let search = poe.trade_search("Standard", &query).await.unwrap();
+        assert_eq!(15, search.total);
+
+        let listings: Vec<_> = poe
+            .trade_fetch_stream(&search)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(14, listings.len());
+        assert_eq!("id12", listings[11].id);
+        assert_eq!(1.0, listings[0].listing.price.as_ref().unwrap().amount);
+    }
+
+    #[tokio::test]
+    async fn trade_fetch_stream_skips_empty_batch() {
+        let transport = Arc::new(InMemoryTransport::new(|request| {
+            let ids: Vec<_> = request.uri().path()["/api/trade/fetch/".len()..]
+                .split(',')
+                .collect();
+            // every listing of the second batch is gone
+            let result: Vec<_> = ids
+                .iter()
+                .map(|id| match id.trim_start_matches("id").parse::<u32>() {
+                    Ok(i) if (10..20).contains(&i) => serde_json::Value::Null,
+                    _ => trade_listing(id),
+                })
+                .collect();
+            let body = serde_json::json!({ "result": result });
+            Response::new(serde_json::to_vec(&body).unwrap())
+        }));
+
+        let poe = PathOfExile::builder()
+            .transport(transport.clone())
+            .build()
+            .unwrap();
+
+        let search = serde_json::from_value(serde_json::json!({
+            "id": "search",
+            "result": (0..25).map(|i| format!("id{i}")).collect::<Vec<_>>(),
+            "total": 25,
+        }))
+        .unwrap();
+
+        let listings: Vec<_> = poe
+            .trade_fetch_stream(&search)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(15, listings.len());
+        assert_eq!("id20", listings[10].id);
+        assert_eq!(3, transport.requests().len());
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn trade_data() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/trade/data/items"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "result": [{
+                    "id": "accessory",
+                    "label": "Accessories",
+                    "entries": [
+                        { "type": "Onyx Amulet", "text": "Onyx Amulet" },
+                        {
+                            "name": "Ashes of the Stars",
+                            "type": "Onyx Amulet",
+                            "text": "Ashes of the Stars Onyx Amulet",
+                            "flags": { "unique": true },
+                        },
+                    ],
+                }],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/trade/data/static"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "result": [{
+                    "id": "Currency",
+                    "label": "Currency",
+                    "entries": [{ "id": "alt", "text": "Orb of Alteration", "image": "/alt.png" }],
+                }, {
+                    "id": "Misc",
+                    "label": null,
+                    "entries": [],
+                }],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/trade/data/leagues"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "result": [{ "id": "Standard", "realm": "pc", "text": "Standard" }],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let poe = poe(&server);
+
+        let items = poe.trade_items().await.unwrap();
+        assert_eq!("accessory", items.category("Onyx Amulet").unwrap().id);
+        assert!(items.category("Leather Belt").is_none());
+
+        let catalog = poe.trade_static().await.unwrap();
+        assert_eq!(Some("alt"), catalog.id("Orb of Alteration"));
+
+        let leagues = poe.trade_leagues().await.unwrap();
+        assert_eq!(Realm::Pc, leagues[0].realm);
+    }
+
+    #[tokio::test]
+    async fn refresh_tokens_use_transport() {
+        let transport = Arc::new(InMemoryTransport::new(|request| {
+            let body = match request.uri().path() {
+                "/oauth/token" => serde_json::json!({
+                    "access_token": "new-access",
+                    "expires_in": 3600,
+                    "token_type": "bearer",
+                    "refresh_token": "new-refresh",
+                }),
+                _ => serde_json::json!({ "leagues": [] }),
+            };
+            Response::new(serde_json::to_vec(&body).unwrap())
+        }));
+
+        let mut expired = TokenSet::new("old-access");
+        expired.refresh_token = Some("old-refresh".to_string());
+        expired.expires_at = Some(chrono::Utc::now());
+
+        let store = Arc::new(InMemoryTokenStore::new());
+        let poe = PathOfExile::builder()
+            .application("test-app", "1.0")
+            .web_url(Url::parse("https://web.example.com").unwrap())
+            .transport(transport.clone())
+            .token(expired)
+            .refresh_tokens(
+                OAuthClient::new("client", "http://127.0.0.1/callback"),
+                store.clone(),
+            )
+            .build()
+            .unwrap();
+
+        poe.account_leagues().await.unwrap();
+        assert_eq!("new-access", store.get().unwrap().access_token);
+
+        let requests = transport.requests();
+        assert_eq!(
+            "https://web.example.com/oauth/token",
+            requests[0].url.as_str()
+        );
+        assert_eq!("test-app/1.0", requests[0].headers["User-Agent"]);
+        assert_eq!("Bearer new-access", requests[1].headers["Authorization"]);
+    }
+
+    #[tokio::test]
+    async fn realm() {
+        let transport = Arc::new(InMemoryTransport::new(|_| Response::new(b"{}".to_vec())));
+        let poe = PathOfExile::builder()
+            .realm(Realm::Xbox)
+            .transport(transport.clone())
+            .build()
+            .unwrap();
+        assert_eq!(&Realm::Xbox, poe.realm());
+
+        let _ = poe.character("SteelDD").await;
+        let _ = poe.stashes("Standard").await;
+        let _ = poe.league("Hardcore").await;
+        let _ = poe.currency_exchange(None).await;
+        let _ = poe.league_ladder("Standard", &LadderQuery::new()).await;
+        let _ = poe
+            .league_ladder("Standard", &LadderQuery::new().realm(Realm::Sony))
+            .await;
+        let _ = poe.with_realm(Realm::Pc).character("SteelDD").await;
+        let _ = poe
+            .with_realm(Realm::Poe2)
+            .public_stashes(Some("1-1"))
+            .await;
+
+        let urls: Vec<_> = transport
+            .requests()
+            .into_iter()
+            .map(|request| request.url.to_string())
+            .collect();
+        assert_eq!(
+            vec![
+                "https://api.pathofexile.com/character/xbox/SteelDD",
+                "https://api.pathofexile.com/stash/xbox/Standard",
+                "https://api.pathofexile.com/league/Hardcore?realm=xbox",
+                "https://api.pathofexile.com/currency-exchange/xbox",
+                "https://api.pathofexile.com/league/Standard/ladder?realm=xbox&limit=200&offset=0",
+                "https://api.pathofexile.com/league/Standard/ladder?realm=sony&limit=200&offset=0",
+                "https://api.pathofexile.com/character/SteelDD",
+                "https://api.pathofexile.com/public-stash-tabs/poe2?id=1-1",
+            ],
+            urls
+        );
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn get_stash_items() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/character-window/get-stash-items"))
+            .and(query_param("accountName", "Steelmage#1234"))
+            .and(query_param("league", "Hardcore & Friends+"))
+            .and(query_param("tabIndex", "2"))
+            .and(query_param("tabs", "1"))
+            .and(query_param("realm", "pc"))
+            .and(header("Cookie", "POESESSID=secret-session"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "numTabs": 1,
+                "tabs": [{
+                    "id": "abc",
+                    "n": "$",
+                    "i": 0,
+                    "type": "CurrencyStash",
+                    "selected": true,
+                    "colour": { "r": 124, "g": 84, "b": 54 },
+                }],
+                "items": [],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let poe = builder(&server)
+            .session_id("secret-session")
+            .build()
+            .unwrap();
+
+        let stash = poe
+            .get_stash_items("Steelmage#1234", "Hardcore & Friends+", 2, true)
+            .await
+            .unwrap();
+        assert_eq!(1, stash.num_tabs);
+        assert_eq!("$", stash.tabs[0].name);
+        assert!(stash.tabs[0].selected);
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn guild_stash() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/guild/Standard/stash/bank"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "stash": {
+                    "id": "bank",
+                    "name": "Bank",
+                    "type": "PremiumStash",
+                    "index": 0,
+                    "metadata": { "colour": "00ff00" },
+                    "items": [],
+                },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let poe = poe(&server);
+
+        let stash = poe.guild_stash("Standard", "bank", None).await.unwrap();
+        assert_eq!("Bank", stash.name);
+        assert_eq!(Some("00ff00"), stash.metadata.colour.as_deref());
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn create_item_filter() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/item-filter"))
+            .and(query_param("validate", "true"))
+            .and(body_partial_json(serde_json::json!({
+                "filter_name": "Team Filter",
+                "realm": "pc",
+                "type": "Normal",
+                "public": false,
+                "filter": "Show",
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "filter": {
+                    "id": "abc",
+                    "filter_name": "Team Filter",
+                    "realm": "pc",
+                    "description": "",
+                    "version": "3.25.0",
+                    "type": "Normal",
+                    "public": false,
+                    "validation": { "valid": true, "version": "3.25.0" },
+                },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/item-filter/abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "filter": {
+                    "id": "abc",
+                    "filter_name": "Team Filter",
+                    "realm": "pc",
+                    "type": "Normal",
+                    "validation": {
+                        "valid": false,
+                        "version": "3.25.0",
+                        "validated": "2024-08-01T12:00:00Z",
+                    },
+                },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let poe = poe(&server);
+
+        let mut filter = ItemFilter {
+            filter_name: "Team Filter".to_string(),
+            filter: Some("Show".to_string()),
+            ..ItemFilter::default()
+        };
+        let created = poe.create_item_filter(&filter, true).await.unwrap();
+        assert_eq!("abc", created.id);
+        assert!(created.validation.unwrap().valid);
+
+        filter.filter = Some("Shwo".to_string());
+        let err = poe
+            .update_item_filter("abc", &filter, true)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            "item filter abc is not valid for game version 3.25.0 \
+             (validated at 2024-08-01T12:00:00+00:00)",
+            err.to_string()
+        );
+        match err {
+            PoeError::InvalidItemFilter { filter, .. } => assert_eq!("abc", filter.id),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn character() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/character/SteelDD"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "character": {
+                    "id": "abc",
+                    "name": "SteelDD",
+                    "realm": "pc",
+                    "class": "Ascendant",
+                    "league": "Standard",
+                    "level": 100,
+                    "experience": 4250334444u64,
+                    "current": true,
+                    "equipment": [],
+                    "passives": {
+                        "hashes": [1, 2, 3],
+                        "hashes_ex": [],
+                        "mastery_effects": { "4": 5 },
+                        "banditChoice": "Alira",
+                        "pantheonMajor": "TheBrineKing",
+                        "jewel_data": [],
+                    },
+                },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let poe = poe(&server);
+
+        let character = poe.character("SteelDD").await.unwrap();
+        assert_eq!("abc", character.id);
+        assert!(character.current);
+        let passives = character.passives.unwrap();
+        assert_eq!(vec![1, 2, 3], passives.hashes);
+        assert_eq!(Some(&5), passives.mastery_effects.get("4"));
+        assert_eq!(Some("Alira"), passives.bandit_choice.as_deref());
+        assert_eq!(Some("TheBrineKing"), passives.pantheon_major.as_deref());
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn stashes() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/stash/Standard"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "stashes": [{
+                    "id": "folder",
+                    "name": "Folder",
+                    "type": "Folder",
+                    "index": 0,
+                    "metadata": { "folder": true },
+                    "children": [{
+                        "id": "maps",
+                        "parent": "folder",
+                        "name": "Maps",
+                        "type": "MapStash",
+                        "metadata": { "colour": "ff0000" },
+                        "children": [{
+                            "id": "tier-1",
+                            "parent": "maps",
+                            "name": "Tier 1",
+                            "type": "MapStash",
+                            "metadata": {},
+                        }],
+                    }],
+                }, {
+                    "id": "currency",
+                    "name": "$",
+                    "type": "CurrencyStash",
+                    "index": 1,
+                    "metadata": { "public": true },
+                }],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/stash/Standard/maps/tier-1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "stash": {
+                    "id": "tier-1",
+                    "parent": "maps",
+                    "name": "Tier 1",
+                    "type": "MapStash",
+                    "metadata": {},
+                    "items": [],
+                },
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let poe = poe(&server);
+
+        let stashes = poe.stashes("Standard").await.unwrap();
+        let ids: Vec<_> = stashes
+            .iter()
+            .flat_map(|stash| stash.iter())
+            .map(|stash| stash.id.as_str())
+            .collect();
+        assert_eq!(vec!["folder", "maps", "tier-1", "currency"], ids);
+        assert!(stashes[0].metadata.folder);
+        assert!(stashes[1].metadata.public);
+
+        let stash = poe.stash("Standard", "maps", Some("tier-1")).await.unwrap();
+        assert_eq!(Some("maps"), stash.parent.as_deref());
+        assert!(stash.items.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn public_stash_stream() {
+        // paused time skips the backoff, which requires a transport without real I/O
+        let transport = Arc::new(InMemoryTransport::new(|request| {
+            let body = match request.uri().query() {
+                Some("id=1-1") => serde_json::json!({
+                    "next_change_id": "2-2",
+                    "stashes": [{
+                        "id": "abc",
+                        "public": true,
+                        "accountName": "Steelmage",
+                        "stash": "~price 1 chaos",
+                        "stashType": "PremiumStash",
+                        "league": "Standard",
+                        "items": [],
+                    }],
+                }),
+                _ => serde_json::json!({ "next_change_id": "2-2", "stashes": [] }),
+            };
+            Response::new(serde_json::to_vec(&body).unwrap())
+        }));
+
+        let poe = PathOfExile::builder()
+            .transport(transport.clone())
+            .build()
+            .unwrap();
+
+        let stream = poe.public_stash_stream(Some("1-1".to_string()));
+        futures::pin_mut!(stream);
+
+        let start = tokio::time::Instant::now();
+        let batch = stream.next().await.unwrap().unwrap();
+        assert_eq!("2-2", batch.next_change_id);
+        assert_eq!(Some("Steelmage"), batch.stashes[0].account_name.as_deref());
+        assert_eq!("PremiumStash", batch.stashes[0].stash_type);
+
+        for _ in 0..2 {
+            let batch = stream.next().await.unwrap().unwrap();
+            assert!(batch.stashes.is_empty());
+        }
+        // the river was caught up after the second batch
+        assert_eq!(super::PUBLIC_STASH_BACKOFF, start.elapsed());
+
+        let requests = transport.requests();
+        assert_eq!(3, requests.len());
+        assert_eq!(Some("id=2-2"), requests[2].url.query());
+    }
 
+    #[cfg(feature = "reqwest-transport")]
     #[ignore]
     #[tokio::test]
     async fn get_characters() {
@@ -174,6 +1926,7 @@ mod tests {
         characters.iter().find(|c| c.name == "SteelDD").unwrap();
     }
 
+    #[cfg(feature = "reqwest-transport")]
     #[ignore]
     #[tokio::test]
     async fn get_items() {
@@ -184,6 +1937,7 @@ mod tests {
         assert_eq!(17, items.items.len());
     }
 
+    #[cfg(feature = "reqwest-transport")]
     #[ignore]
     #[tokio::test]
     async fn get_passives() {
@@ -197,6 +1951,7 @@ mod tests {
         assert!(passives.skill_tree_data.is_none());
     }
 
+    #[cfg(feature = "reqwest-transport")]
     #[ignore]
     #[tokio::test]
     async fn get_passives_with_data() {
@@ -210,13 +1965,14 @@ mod tests {
         assert!(passives.skill_tree_data.is_some());
     }
 
+    #[cfg(feature = "reqwest-transport")]
     #[ignore]
     #[tokio::test]
     async fn leagues() {
         let poe = PathOfExile::new();
 
         let all = poe.leagues(50, 0).await.unwrap();
-        assert_eq!("Standard", all.get(0).unwrap().id);
+        assert_eq!("Standard", all.first().unwrap().id);
         assert_eq!("Hardcore", all.get(1).unwrap().id);
         assert_eq!("SSF Standard", all.get(2).unwrap().id);
         assert_eq!("SSF Hardcore", all.get(3).unwrap().id);
@@ -225,15 +1981,16 @@ mod tests {
         let hardcore = poe.leagues(1, 1).await.unwrap();
 
         assert_eq!(standard.len(), 1);
-        assert_eq!("Standard", standard.get(0).unwrap().id);
+        assert_eq!("Standard", standard.first().unwrap().id);
         assert_eq!(hardcore.len(), 1);
-        assert_eq!("Hardcore", hardcore.get(0).unwrap().id);
+        assert_eq!("Hardcore", hardcore.first().unwrap().id);
 
         let ssf = poe.leagues(2, 2).await.unwrap();
-        assert_eq!("SSF Standard", ssf.get(0).unwrap().id);
+        assert_eq!("SSF Standard", ssf.first().unwrap().id);
         assert_eq!("SSF Hardcore", ssf.get(1).unwrap().id);
     }
 
+    #[cfg(feature = "reqwest-transport")]
     #[tokio::test]
     async fn ladder() {
         let poe = PathOfExile::new();
@@ -242,9 +1999,10 @@ mod tests {
 
         assert_eq!(15000, ladder.total);
         assert_eq!(1, ladder.entries.len());
-        assert_eq!(1, ladder.entries.get(0).unwrap().rank);
+        assert_eq!(1, ladder.entries.first().unwrap().rank);
     }
 
+    #[cfg(feature = "reqwest-transport")]
     #[ignore]
     #[tokio::test]
     async fn ladder_rate_limit() {
@@ -259,7 +2017,7 @@ mod tests {
                 let ladder = poe.ladder("Standard", 1, 0).await.unwrap();
                 assert_eq!(15000, ladder.total);
                 assert_eq!(1, ladder.entries.len());
-                assert_eq!(1, ladder.entries.get(0).unwrap().rank);
+                assert_eq!(1, ladder.entries.first().unwrap().rank);
             }));
         }
 
@@ -0,0 +1,189 @@
+use std::fmt;
+use tokio::sync::Mutex;
+
+use crate::oauth::{OAuthClient, Scope, TokenSet, TokenStore};
+use crate::response::{PoeError, PoeResult};
+
+/// Tokens are renewed this long before they actually expire.
+const EXPIRY_LEEWAY_SECS: i64 = 60;
+
+fn leeway() -> chrono::Duration {
+    chrono::Duration::seconds(EXPIRY_LEEWAY_SECS)
+}
+
+/// The `POESESSID` cookie of a logged in website session.
+///
+/// Allows the legacy character window endpoints to access private profiles of the
+/// account the session belongs to. The session id grants full access to the account,
+/// it is never included in `Debug` output.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SessionId(String);
+
+impl SessionId {
+    pub fn new(session_id: impl Into<String>) -> Self {
+        Self(session_id.into())
+    }
+
+    /// Returns the session id itself, take care to not expose it.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SessionId {
+    fn from(session_id: String) -> Self {
+        Self::new(session_id)
+    }
+}
+
+impl From<&str> for SessionId {
+    fn from(session_id: &str) -> Self {
+        Self::new(session_id)
+    }
+}
+
+impl fmt::Debug for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SessionId(<redacted>)")
+    }
+}
+
+/// Provides the access token attached to every request.
+pub(crate) enum Authenticator {
+    /// A fixed token, e.g. obtained through the authorization code flow.
+    Static(TokenSet),
+    /// Tokens requested on demand with the client credentials grant.
+    ClientCredentials {
+        oauth: Box<OAuthClient>,
+        scopes: Vec<Scope>,
+        token: Mutex<Option<TokenSet>>,
+    },
+    /// Tokens which are refreshed with their refresh token and persisted in a store.
+    Refreshing {
+        oauth: Box<OAuthClient>,
+        store: Box<dyn TokenStore>,
+        token: Mutex<Option<TokenSet>>,
+    },
+}
+
+impl Authenticator {
+    pub(crate) fn client_credentials(oauth: OAuthClient, scopes: Vec<Scope>) -> Self {
+        Self::ClientCredentials {
+            oauth: Box::new(oauth),
+            scopes,
+            token: Mutex::new(None),
+        }
+    }
+
+    /// Creates an authenticator which refreshes tokens, without an initial token
+    /// the token is loaded from the store on first use.
+    pub(crate) fn refreshing(
+        oauth: OAuthClient,
+        store: Box<dyn TokenStore>,
+        token: Option<TokenSet>,
+    ) -> Self {
+        Self::Refreshing {
+            oauth: Box::new(oauth),
+            store,
+            token: Mutex::new(token),
+        }
+    }
+
+    /// Returns a valid access token, requesting a new one if necessary.
+    pub(crate) async fn access_token(&self) -> PoeResult<String> {
+        // holding the token lock while requesting a new token makes sure
+        // concurrent requests only trigger a single token request
+        match self {
+            Self::Static(token) => Ok(token.access_token.clone()),
+            Self::ClientCredentials {
+                oauth,
+                scopes,
+                token,
+            } => {
+                let mut token = token.lock().await;
+
+                match token.as_ref() {
+                    Some(token) if !token.expires_within(leeway()) => {
+                        Ok(token.access_token.clone())
+                    }
+                    _ => {
+                        let new_token = oauth.client_credentials(scopes).await?;
+                        let access_token = new_token.access_token.clone();
+                        *token = Some(new_token);
+                        Ok(access_token)
+                    }
+                }
+            }
+            Self::Refreshing {
+                oauth,
+                store,
+                token,
+            } => {
+                let mut token = token.lock().await;
+
+                if token.is_none() {
+                    *token = store.load().await?;
+                }
+
+                let current = token.as_ref().ok_or(PoeError::MissingToken)?;
+                let refresh_token = match current.refresh_token.as_ref() {
+                    Some(refresh_token) if current.expires_within(leeway()) => refresh_token,
+                    // not expired yet or nothing we can do about it
+                    _ => return Ok(current.access_token.clone()),
+                };
+
+                let new_token = oauth.refresh(refresh_token).await?;
+                store.store(&new_token).await?;
+
+                let access_token = new_token.access_token.clone();
+                *token = Some(new_token);
+                Ok(access_token)
+            }
+        }
+    }
+
+    /// Discards the access token after it was rejected by the API.
+    ///
+    /// Returns `true` if a new token can be obtained and the request should be retried.
+    pub(crate) async fn reject(&self, access_token: &str) -> bool {
+        match self {
+            Self::Static(_) => false,
+            Self::ClientCredentials { token, .. } => {
+                let mut token = token.lock().await;
+                // another request might already have replaced the token
+                if token.as_ref().map(|t| t.access_token.as_str()) == Some(access_token) {
+                    *token = None;
+                }
+                true
+            }
+            Self::Refreshing { token, .. } => {
+                let mut token = token.lock().await;
+                match token.as_mut() {
+                    Some(token) if token.access_token == access_token => {
+                        // force a refresh with the next request
+                        token.expires_at = Some(chrono::Utc::now());
+                        token.refresh_token.is_some()
+                    }
+                    Some(_) => true,
+                    None => false,
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Authenticator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Static(token) => f.debug_tuple("Static").field(token).finish(),
+            Self::ClientCredentials { oauth, scopes, .. } => f
+                .debug_struct("ClientCredentials")
+                .field("oauth", oauth)
+                .field("scopes", scopes)
+                .finish(),
+            Self::Refreshing { oauth, .. } => {
+                f.debug_struct("Refreshing").field("oauth", oauth).finish()
+            }
+        }
+    }
+}
@@ -1,223 +1,593 @@
-use reqwest::{Client, Url};
+use http::header::{AUTHORIZATION, CONTENT_TYPE, COOKIE, USER_AGENT};
+use http::{HeaderValue, Method, StatusCode};
 use serde::de::DeserializeOwned;
 use serde::ser::Serialize;
-use std::collections::{HashMap, LinkedList};
-use std::future::Future;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::convert::TryFrom;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tokio::sync::{RwLock, Semaphore, SemaphorePermit};
-use tokio::time::sleep_until;
+use url::Url;
 
+use crate::auth::{Authenticator, SessionId};
+use crate::oauth::{OAuthClient, Scope, TokenSet, TokenStore};
+use crate::rate_limit::{self, RateLimiter};
 use crate::response::{ApiErrorResponse, PoeError, PoeResult};
+use crate::transport::{Request, Transport};
 
 const API_URL: &str = "https://api.pathofexile.com";
+const WEB_URL: &str = "https://www.pathofexile.com";
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// The domain a request is made to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Domain {
+    /// The API domain, `https://api.pathofexile.com` by default.
+    Api,
+    /// The website domain, `https://www.pathofexile.com` by default.
+    Web,
+}
+
+/// Makes sure relative paths are appended to the base URL instead of replacing its path.
+fn base_url(mut url: Url) -> Url {
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    url
+}
 
 pub struct PoeClient {
-    client: Client,
-    base_url: Url,
+    transport: Arc<dyn Transport>,
+    api_url: Url,
+    web_url: Url,
     rate_limiter: RateLimiter,
     user_agent: String,
-}
-
-impl Default for PoeClient {
-    fn default() -> Self {
-        Self::new()
-    }
+    max_retries: u32,
+    authenticator: Option<Authenticator>,
+    session_id: Option<SessionId>,
 }
 
 impl PoeClient {
-    pub fn new() -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .unwrap();
-
+    pub fn new(transport: Arc<dyn Transport>) -> Self {
         PoeClient {
-            client,
-            base_url: Url::parse(API_URL).unwrap(),
+            transport,
+            api_url: base_url(Url::parse(API_URL).unwrap()),
+            web_url: base_url(Url::parse(WEB_URL).unwrap()),
             rate_limiter: RateLimiter::new(),
             user_agent: format!("poe-rs/{}", env!("CARGO_PKG_VERSION")),
+            max_retries: DEFAULT_MAX_RETRIES,
+            authenticator: None,
+            session_id: None,
         }
     }
 
+    pub fn transport(&mut self, transport: Arc<dyn Transport>) {
+        self.transport = transport;
+    }
+
+    /// The transport used to send requests, shared with OAuth clients created by the builder.
+    pub fn shared_transport(&self) -> Arc<dyn Transport> {
+        self.transport.clone()
+    }
+
     pub fn user_agent(&mut self, user_agent: impl Into<String>) {
         self.user_agent = user_agent.into();
     }
 
-    pub async fn get<T: DeserializeOwned>(&self, call_id: &str, url: &str) -> PoeResult<T> {
-        let url = Url::options()
-            .base_url(Some(&self.base_url))
-            .parse(url)
-            .unwrap();
+    pub fn api_url(&mut self, url: Url) {
+        self.api_url = base_url(url);
+    }
+
+    pub fn web_url(&mut self, url: Url) {
+        self.web_url = base_url(url);
+    }
+
+    /// Resolves a path relative to the base URL of the domain.
+    pub fn url(&self, domain: Domain, path: &str) -> Url {
+        let base_url = match domain {
+            Domain::Api => &self.api_url,
+            Domain::Web => &self.web_url,
+        };
+
+        base_url.join(path.trim_start_matches('/')).unwrap()
+    }
+
+    pub fn max_retries(&mut self, max_retries: u32) {
+        self.max_retries = max_retries;
+    }
+
+    pub fn token(&mut self, token: TokenSet) {
+        self.authenticator = Some(Authenticator::Static(token));
+    }
 
-        self.execute(call_id, self.client.get(url)).await
+    pub fn client_credentials(&mut self, oauth: OAuthClient, scopes: Vec<Scope>) {
+        self.authenticator = Some(Authenticator::client_credentials(oauth, scopes));
+    }
+
+    pub fn refresh_tokens(
+        &mut self,
+        oauth: OAuthClient,
+        store: Box<dyn TokenStore>,
+        token: Option<TokenSet>,
+    ) {
+        self.authenticator = Some(Authenticator::refreshing(oauth, store, token));
+    }
+
+    /// Sends the `POESESSID` cookie with all requests to the website.
+    pub fn session_id(&mut self, session_id: SessionId) {
+        self.session_id = Some(session_id);
+    }
+
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        call_id: &str,
+        domain: Domain,
+        path: &str,
+    ) -> PoeResult<T> {
+        let url = self.url(domain, path);
+
+        self.execute(call_id, Method::GET, domain, url, None).await
     }
 
     pub async fn post<T: DeserializeOwned, Body: Serialize + ?Sized>(
         &self,
         call_id: &str,
-        url: &str,
+        domain: Domain,
+        path: &str,
         body: &Body,
     ) -> PoeResult<T> {
-        let url = Url::options()
-            .base_url(Some(&self.base_url))
-            .parse(url)
-            .unwrap();
+        let url = self.url(domain, path);
+        let body = serde_json::to_vec(body)?;
 
-        self.execute(call_id, self.client.post(url).json(body))
+        self.execute(call_id, Method::POST, domain, url, Some(body))
             .await
     }
 
+    fn request(
+        &self,
+        method: &Method,
+        domain: Domain,
+        url: &Url,
+        body: Option<&Vec<u8>>,
+        access_token: Option<&str>,
+    ) -> PoeResult<Request> {
+        let mut request = http::Request::builder()
+            .method(method.clone())
+            .uri(url.as_str())
+            .header(USER_AGENT, &self.user_agent);
+        if let Some(access_token) = access_token {
+            request = request.header(AUTHORIZATION, format!("Bearer {access_token}"));
+        }
+        if body.is_some() {
+            request = request.header(CONTENT_TYPE, "application/json");
+        }
+        if let (Domain::Web, Some(session_id)) = (domain, self.session_id.as_ref()) {
+            let mut cookie = HeaderValue::try_from(format!("POESESSID={}", session_id.expose()))
+                .map_err(|err| PoeError::Transport(Box::new(err)))?;
+            // keeps the session id out of the debug output of requests
+            cookie.set_sensitive(true);
+            request = request.header(COOKIE, cookie);
+        }
+
+        request
+            .body(body.cloned().unwrap_or_default())
+            .map_err(|err| PoeError::Transport(Box::new(err)))
+    }
+
     async fn execute<T: DeserializeOwned>(
         &self,
         call_id: &str,
-        request: reqwest::RequestBuilder,
+        method: Method,
+        domain: Domain,
+        url: Url,
+        body: Option<Vec<u8>>,
     ) -> PoeResult<T> {
-        let request = request.header("User-Agent", &self.user_agent);
+        let mut retries = 0;
+        let mut reauthenticated = false;
+
+        let response = loop {
+            let access_token = match self.authenticator.as_ref() {
+                Some(authenticator) => Some(authenticator.access_token().await?),
+                None => None,
+            };
+            let request = self.request(
+                &method,
+                domain,
+                &url,
+                body.as_ref(),
+                access_token.as_deref(),
+            )?;
+
+            let response = self
+                .rate_limiter
+                .rate_limited(call_id, self.transport.send(request))
+                .await?;
+
+            if response.status() == StatusCode::UNAUTHORIZED && !reauthenticated {
+                if let (Some(authenticator), Some(access_token)) =
+                    (self.authenticator.as_ref(), access_token.as_ref())
+                {
+                    if authenticator.reject(access_token).await {
+                        reauthenticated = true;
+                        continue;
+                    }
+                }
+            }
 
-        let response = self
-            .rate_limiter
-            .rate_limited(call_id, async { request.send().await })
-            .await
-            .map_err(PoeError::from)?;
+            if response.status() != StatusCode::TOO_MANY_REQUESTS {
+                break response;
+            }
+
+            // the rate limiter already delays the next request accordingly
+            if retries >= self.max_retries {
+                let retry_after = rate_limit::retry_after(response.headers());
+                return Err(PoeError::RateLimited { retry_after });
+            }
+            retries += 1;
+        };
+
+        // strip BOM, which is sometimes included
+        let body = response.body();
+        let body = body.strip_prefix("\u{feff}".as_bytes()).unwrap_or(body);
 
         if response.status().is_success() {
-            return response
-                .text()
-                .await
-                .map_err(PoeError::from)
-                .and_then(|raw| {
-                    // strip BOM, which is sometimes included
-                    serde_json::from_str::<T>(raw.trim_start_matches('\u{feff}'))
-                        .map_err(PoeError::from)
-                });
+            return serde_json::from_slice::<T>(body).map_err(PoeError::from);
         }
 
-        match response.json::<ApiErrorResponse>().await {
-            Ok(error) => Err(PoeError::from(error.error)),
-            Err(error) => Err(PoeError::from(error)),
+        let status = response.status().as_u16();
+        match serde_json::from_slice::<ApiErrorResponse>(body) {
+            Ok(ApiErrorResponse { mut error }) => {
+                error.status = status;
+                error.url = url.to_string();
+                Err(PoeError::from(error))
+            }
+            Err(_) => Err(PoeError::UnexpectedStatus {
+                status,
+                url: url.to_string(),
+            }),
         }
     }
 }
 
-#[derive(Debug)]
-struct History {
-    calls: Arc<std::sync::Mutex<LinkedList<Instant>>>,
-    duration: Duration,
-    limit: usize,
-    tickets: Semaphore,
-    active: AtomicUsize,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oauth::InMemoryTokenStore;
+    use crate::transport::InMemoryTransport;
+    #[cfg(feature = "reqwest-transport")]
+    use crate::transport::ReqwestTransport;
+    use serde_json::Value;
+    use std::sync::Arc;
+    #[cfg(feature = "reqwest-transport")]
+    use std::time::Duration;
+    #[cfg(feature = "reqwest-transport")]
+    use wiremock::matchers::{body_string_contains, header, method, path};
+    #[cfg(feature = "reqwest-transport")]
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[cfg(feature = "reqwest-transport")]
+    fn client(server: &MockServer) -> PoeClient {
+        let mut client = PoeClient::new(Arc::new(ReqwestTransport::default()));
+        client.api_url(Url::parse(&server.uri()).unwrap());
+        client
+    }
 
-impl History {
-    fn new() -> History {
-        History {
-            calls: Arc::new(std::sync::Mutex::new(LinkedList::new())),
-            duration: Duration::from_secs(5),
-            limit: 5,
-            tickets: Semaphore::new(5),
-            active: AtomicUsize::new(0),
-        }
+    #[test]
+    fn url() {
+        let (mut client, _) = in_memory(200, "{}");
+        assert_eq!(
+            "https://api.pathofexile.com/ladders/Standard?limit=1",
+            client
+                .url(Domain::Api, "/ladders/Standard?limit=1")
+                .as_str()
+        );
+        assert_eq!(
+            "https://www.pathofexile.com/character-window/get-characters",
+            client
+                .url(Domain::Web, "/character-window/get-characters")
+                .as_str()
+        );
+
+        client.api_url(Url::parse("http://127.0.0.1:8080/proxy").unwrap());
+        assert_eq!(
+            "http://127.0.0.1:8080/proxy/leagues",
+            client.url(Domain::Api, "/leagues").as_str()
+        );
     }
 
-    // fn from(header: &str) -> History {
-    //     let mut limit_header = header
-    //         .split(',')
-    //         .next().unwrap()
-    //         .split(':')
-    //         .map(|val| val.parse::<u32>().unwrap());
+    #[cfg(feature = "reqwest-transport")]
+    fn too_many_requests() -> ResponseTemplate {
+        ResponseTemplate::new(429).insert_header("Retry-After", "0")
+    }
 
-    //     let limit = limit_header.next().unwrap();
-    //     let duration = limit_header.next().unwrap();
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn bearer_token() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header("Authorization", "Bearer access"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut client = client(&server);
+        client.token(TokenSet::new("access"));
+        let result = client.get::<Value>("test", Domain::Api, "/").await;
+        assert!(result.is_ok());
+    }
 
-    //     History {
-    //         calls: Arc::new(Mutex::new(LinkedList::new())),
-    //         duration: Duration::from_secs(duration as u64),
-    //         limit,
-    //         tickets: Semaphore::new(limit as usize),
-    //         tickets2: AtomicUsize::new(0)
-    //     }
-    // }
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn client_credentials() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "access",
+                "expires_in": null,
+                "token_type": "bearer",
+                "scope": "service:psapi",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(header("Authorization", "Bearer access"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let token_url = Url::parse(&format!("{}/oauth/token", server.uri())).unwrap();
+        let oauth = OAuthClient::confidential("client", "secret").token_url(token_url);
+
+        let mut client = client(&server);
+        client.client_credentials(oauth, vec![Scope::ServicePsapi]);
+        // the token is cached for the second request
+        assert!(client.get::<Value>("test", Domain::Api, "/").await.is_ok());
+        assert!(client.get::<Value>("test", Domain::Api, "/").await.is_ok());
+    }
 
-    fn done(&self) {
-        self.calls.lock().unwrap().push_back(Instant::now());
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn client_credentials_unauthorized() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "revoked",
+                "token_type": "bearer",
+            })))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "access",
+                "token_type": "bearer",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(header("Authorization", "Bearer revoked"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(serde_json::json!({
+                "error": { "code": 8, "message": "Unauthorized" }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(header("Authorization", "Bearer access"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let token_url = Url::parse(&format!("{}/oauth/token", server.uri())).unwrap();
+        let oauth = OAuthClient::confidential("client", "secret").token_url(token_url);
+
+        let mut client = client(&server);
+        client.client_credentials(oauth, vec![Scope::ServicePsapi]);
+        assert!(client.get::<Value>("test", Domain::Api, "/").await.is_ok());
     }
 
-    fn failed(&self) {
-        self.active.fetch_sub(1, Ordering::Relaxed);
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn refresh_expired_token() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .and(body_string_contains("refresh_token=old-refresh"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "new-access",
+                "expires_in": 36000,
+                "token_type": "bearer",
+                "refresh_token": "new-refresh",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(header("Authorization", "Bearer new-access"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+            .expect(5)
+            .mount(&server)
+            .await;
+
+        let token_url = Url::parse(&format!("{}/oauth/token", server.uri())).unwrap();
+        let oauth = OAuthClient::new("client", "http://127.0.0.1/callback").token_url(token_url);
+
+        let mut expired = TokenSet::new("old-access");
+        expired.refresh_token = Some("old-refresh".to_string());
+        expired.expires_at = Some(chrono::Utc::now());
+
+        let store = Arc::new(InMemoryTokenStore::new());
+        let mut client = client(&server);
+        client.refresh_tokens(oauth, Box::new(store.clone()), Some(expired));
+
+        // concurrent requests only refresh the token once
+        let requests = (0..5).map(|_| client.get::<Value>("test", Domain::Api, "/"));
+        for result in futures::future::join_all(requests).await {
+            assert!(result.is_ok());
+        }
+
+        let stored = store.get().unwrap();
+        assert_eq!("new-access", stored.access_token);
+        assert_eq!(Some("new-refresh"), stored.refresh_token.as_deref());
     }
 
-    async fn wait(&self) -> SemaphorePermit<'_> {
-        let ticket = self.tickets.acquire().await.unwrap();
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn refresh_token_from_store() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header("Authorization", "Bearer stored"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let oauth = OAuthClient::new("client", "http://127.0.0.1/callback");
+        let store = InMemoryTokenStore::with_token(TokenSet::new("stored"));
+
+        let mut client = client(&server);
+        client.refresh_tokens(oauth, Box::new(store), None);
+        assert!(client.get::<Value>("test", Domain::Api, "/").await.is_ok());
+    }
 
-        loop {
-            let mut wait_time = None;
+    #[tokio::test]
+    async fn refresh_token_missing() {
+        let oauth = OAuthClient::new("client", "http://127.0.0.1/callback");
 
-            if self.active.fetch_add(1, Ordering::SeqCst) < self.limit {
-                return ticket;
-            } else {
-                self.active.fetch_sub(1, Ordering::SeqCst);
+        let (mut client, _) = in_memory(200, "{}");
+        client.refresh_tokens(oauth, Box::new(InMemoryTokenStore::new()), None);
+        let result = client.get::<Value>("test", Domain::Api, "/").await;
+        assert!(matches!(result, Err(PoeError::MissingToken)));
+    }
 
-                let mut calls = self.calls.lock().unwrap();
-                if let Some(time) = calls.front() {
-                    let x = Instant::now().checked_sub(self.duration).unwrap();
-                    if x > *time {
-                        calls.pop_front();
-                        self.active.fetch_sub(1, Ordering::SeqCst);
-                    } else {
-                        wait_time = Some(tokio::time::Instant::from_std(*time + self.duration));
-                    }
-                }
+    fn in_memory(status: u16, body: &'static str) -> (PoeClient, Arc<InMemoryTransport>) {
+        let transport = Arc::new(InMemoryTransport::new(move |_| {
+            http::Response::builder()
+                .status(status)
+                .body(body.as_bytes().to_vec())
+                .unwrap()
+        }));
 
-                drop(calls);
-            }
+        (PoeClient::new(transport.clone()), transport)
+    }
 
-            if let Some(time) = wait_time {
-                sleep_until(time).await;
-            }
-        }
+    #[tokio::test]
+    async fn session_id() {
+        let (mut client, transport) = in_memory(200, "{}");
+        client.session_id(SessionId::new("secret-session"));
+
+        client.get::<Value>("test", Domain::Web, "/").await.unwrap();
+        client.get::<Value>("test", Domain::Api, "/").await.unwrap();
+
+        let requests = transport.requests();
+        assert_eq!("POESESSID=secret-session", requests[0].headers[COOKIE]);
+        assert!(!requests[1].headers.contains_key(COOKIE));
+        assert!(!format!("{:?}", requests).contains("secret-session"));
+        assert!(!format!("{:?}", SessionId::new("secret-session")).contains("secret-session"));
     }
-}
 
-#[derive(Debug)]
-struct RateLimiter {
-    limits: Arc<RwLock<HashMap<String, History>>>,
-}
+    #[tokio::test]
+    async fn in_memory_request() {
+        let (mut client, transport) = in_memory(200, "\u{feff}{\"foo\": 1}");
+        client.user_agent("test/1.0");
+        client.token(TokenSet::new("access"));
+
+        let result = client
+            .post::<Value, _>(
+                "test",
+                Domain::Api,
+                "/foo?bar=1",
+                &serde_json::json!({"a": 1}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(serde_json::json!({"foo": 1}), result);
+
+        let requests = transport.requests();
+        assert_eq!(1, requests.len());
+        assert_eq!(Method::POST, requests[0].method);
+        assert_eq!(
+            "https://api.pathofexile.com/foo?bar=1",
+            requests[0].url.as_str()
+        );
+        assert_eq!("test/1.0", requests[0].headers[USER_AGENT]);
+        assert_eq!("Bearer access", requests[0].headers[AUTHORIZATION]);
+        assert_eq!("application/json", requests[0].headers[CONTENT_TYPE]);
+        assert_eq!(b"{\"a\":1}", requests[0].body.as_slice());
+    }
 
-impl RateLimiter {
-    fn new() -> Self {
-        Self {
-            limits: Arc::new(RwLock::new(HashMap::new())),
+    #[tokio::test]
+    async fn in_memory_api_error() {
+        let (client, _) = in_memory(
+            404,
+            r#"{"error": {"code": 1, "message": "Resource not found"}}"#,
+        );
+
+        let result = client
+            .get::<Value>("test", Domain::Api, "/ladders/Foo")
+            .await;
+        match result {
+            Err(PoeError::NotFound(error)) => {
+                assert_eq!(404, error.status);
+                assert_eq!("https://api.pathofexile.com/ladders/Foo", error.url);
+                assert_eq!("Resource not found", error.message);
+            }
+            other => panic!("unexpected result {:?}", other),
         }
     }
 
-    async fn rate_limited(
-        &self,
-        call_id: &str,
-        api_call: impl Future<Output = reqwest::Result<reqwest::Response>>,
-    ) -> reqwest::Result<reqwest::Response> {
-        if !self.limits.read().await.contains_key(call_id) {
-            self.limits
-                .write()
-                .await
-                .entry(call_id.to_string())
-                .or_insert_with(History::new);
-        }
+    #[tokio::test]
+    async fn in_memory_unexpected_status() {
+        let (client, _) = in_memory(502, "<html>Bad Gateway</html>");
+
+        let result = client.get::<Value>("test", Domain::Api, "/").await;
+        assert!(matches!(
+            result,
+            Err(PoeError::UnexpectedStatus { status: 502, .. })
+        ));
+    }
 
-        let limits = self.limits.read().await;
-        let history = limits.get(call_id).unwrap();
-        let _ticket = history.wait().await;
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn retry_rate_limited() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(too_many_requests())
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = client(&server);
+        let result = client.get::<Value>("test", Domain::Api, "/").await;
+        assert!(result.is_ok());
+    }
 
-        match api_call.await {
-            Ok(result) => {
-                history.done();
-                Ok(result)
-            }
-            Err(err) => {
-                history.failed();
-                Err(err)
-            }
-        }
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn retry_budget_exhausted() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(too_many_requests())
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let mut client = client(&server);
+        client.max_retries(1);
+        let result = client.get::<Value>("test", Domain::Api, "/").await;
+        assert!(matches!(
+            result,
+            Err(PoeError::RateLimited { retry_after }) if retry_after == Duration::from_secs(0)
+        ));
     }
 }
@@ -1,16 +1,35 @@
 pub mod api;
-#[cfg(feature = "client")]
+#[cfg(feature = "client-core")]
 mod api_client;
-#[cfg(feature = "client")]
+#[cfg(feature = "client-core")]
+mod auth;
+#[cfg(feature = "client-core")]
 mod client;
-#[cfg(feature = "client")]
+#[cfg(feature = "client-core")]
+pub mod oauth;
+#[cfg(feature = "client-core")]
 pub mod page;
-#[cfg(feature = "client")]
+#[cfg(feature = "client-core")]
+mod query;
+#[cfg(feature = "client-core")]
+mod rate_limit;
+#[cfg(feature = "client-core")]
 pub mod response;
+#[cfg(feature = "client-core")]
+pub mod river;
+pub mod trade;
+#[cfg(feature = "client-core")]
+pub mod transport;
 mod utils;
 
 pub use crate::api::*;
-#[cfg(feature = "client")]
-pub use crate::api_client::PathOfExile;
-#[cfg(feature = "client")]
+#[cfg(feature = "client-core")]
+pub use crate::api_client::{PathOfExile, PathOfExileBuilder};
+#[cfg(feature = "client-core")]
+pub use crate::auth::SessionId;
+#[cfg(feature = "client-core")]
+pub use crate::query::{LadderQuery, LeaguesQuery, PvpMatchesQuery};
+#[cfg(feature = "client-core")]
 pub use crate::response::*;
+#[cfg(feature = "client-core")]
+pub use url::Url;
@@ -0,0 +1,1076 @@
+//! OAuth 2.1 support for the Path of Exile API.
+//!
+//! Most of the API (profile, characters, stashes, item filters) requires an access token,
+//! which can be obtained through the authorization code flow with PKCE:
+//!
+//! ```no_run
+//! # async fn run() -> poe_api::PoeResult<()> {
+//! use poe_api::oauth::{LoopbackRedirect, OAuthClient, Scope};
+//! use poe_api::PathOfExile;
+//!
+//! let redirect = LoopbackRedirect::bind(0).await?;
+//! let oauth = OAuthClient::new("my-client-id", redirect.redirect_uri());
+//!
+//! let request = oauth.authorize(&[Scope::AccountProfile, Scope::AccountCharacters]);
+//! println!("Please open {}", request.url());
+//!
+//! let code = redirect.receive_code(request.state()).await?;
+//! let token = oauth.exchange_code(&request, &code).await?;
+//!
+//! let poe = PathOfExile::builder().token(token).build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Access tokens expire after a few hours, with [`crate::PathOfExileBuilder::refresh_tokens`]
+//! they are refreshed automatically and persisted in a [`TokenStore`].
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use futures::future::{self, BoxFuture, Either};
+use futures::stream::{FuturesUnordered, StreamExt};
+use http::header::{CONTENT_TYPE, USER_AGENT};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use url::Url;
+
+use crate::response::{PoeError, PoeResult};
+use crate::transport::{default_transport, Transport};
+use crate::utils::write_file_atomic;
+
+const AUTHORIZE_URL: &str = "https://www.pathofexile.com/oauth/authorize";
+const TOKEN_URL: &str = "https://www.pathofexile.com/oauth/token";
+
+/// Permissions which can be requested from an account.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", from = "String")]
+pub enum Scope {
+    /// `account:profile`
+    AccountProfile,
+    /// `account:leagues`
+    AccountLeagues,
+    /// `account:stashes`
+    AccountStashes,
+    /// `account:guild:stashes`
+    AccountGuildStashes,
+    /// `account:characters`
+    AccountCharacters,
+    /// `account:league_accounts`
+    AccountLeagueAccounts,
+    /// `account:item_filter`
+    AccountItemFilter,
+    /// `service:leagues`, only available through the client credentials grant
+    ServiceLeagues,
+    /// `service:leagues:ladder`, only available through the client credentials grant
+    ServiceLeaguesLadder,
+    /// `service:pvp_matches`, only available through the client credentials grant
+    ServicePvpMatches,
+    /// `service:pvp_matches:ladder`, only available through the client credentials grant
+    ServicePvpMatchesLadder,
+    /// `service:psapi`, only available through the client credentials grant
+    ServicePsapi,
+    /// `service:cxapi`, only available through the client credentials grant
+    ServiceCxapi,
+    /// A scope unknown to this library.
+    Other(String),
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::AccountProfile => "account:profile",
+            Self::AccountLeagues => "account:leagues",
+            Self::AccountStashes => "account:stashes",
+            Self::AccountGuildStashes => "account:guild:stashes",
+            Self::AccountCharacters => "account:characters",
+            Self::AccountLeagueAccounts => "account:league_accounts",
+            Self::AccountItemFilter => "account:item_filter",
+            Self::ServiceLeagues => "service:leagues",
+            Self::ServiceLeaguesLadder => "service:leagues:ladder",
+            Self::ServicePvpMatches => "service:pvp_matches",
+            Self::ServicePvpMatchesLadder => "service:pvp_matches:ladder",
+            Self::ServicePsapi => "service:psapi",
+            Self::ServiceCxapi => "service:cxapi",
+            Self::Other(scope) => scope,
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&str> for Scope {
+    fn from(scope: &str) -> Self {
+        match scope {
+            "account:profile" => Self::AccountProfile,
+            "account:leagues" => Self::AccountLeagues,
+            "account:stashes" => Self::AccountStashes,
+            "account:guild:stashes" => Self::AccountGuildStashes,
+            "account:characters" => Self::AccountCharacters,
+            "account:league_accounts" => Self::AccountLeagueAccounts,
+            "account:item_filter" => Self::AccountItemFilter,
+            "service:leagues" => Self::ServiceLeagues,
+            "service:leagues:ladder" => Self::ServiceLeaguesLadder,
+            "service:pvp_matches" => Self::ServicePvpMatches,
+            "service:pvp_matches:ladder" => Self::ServicePvpMatchesLadder,
+            "service:psapi" => Self::ServicePsapi,
+            "service:cxapi" => Self::ServiceCxapi,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for Scope {
+    fn from(scope: String) -> Self {
+        Self::from(scope.as_str())
+    }
+}
+
+impl From<Scope> for String {
+    fn from(scope: Scope) -> Self {
+        scope.as_str().to_string()
+    }
+}
+
+impl FromStr for Scope {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from(s))
+    }
+}
+
+fn join_scopes(scopes: &[Scope]) -> String {
+    scopes
+        .iter()
+        .map(Scope::as_str)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn random_string(bytes: usize) -> String {
+    let mut buf = vec![0; bytes];
+    getrandom::getrandom(&mut buf).expect("no source of randomness available");
+    URL_SAFE_NO_PAD.encode(buf)
+}
+
+/// A PKCE code verifier and its `S256` challenge.
+#[derive(Clone)]
+pub struct Pkce {
+    verifier: String,
+    challenge: String,
+}
+
+impl Pkce {
+    /// Generates a new random code verifier.
+    pub fn new() -> Self {
+        Self::from_verifier(random_string(32))
+    }
+
+    /// Creates the challenge for an existing code verifier.
+    pub fn from_verifier(verifier: impl Into<String>) -> Self {
+        let verifier = verifier.into();
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+
+        Self {
+            verifier,
+            challenge,
+        }
+    }
+
+    pub fn verifier(&self) -> &str {
+        &self.verifier
+    }
+
+    pub fn challenge(&self) -> &str {
+        &self.challenge
+    }
+}
+
+impl Default for Pkce {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Pkce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pkce")
+            .field("verifier", &"<redacted>")
+            .field("challenge", &self.challenge)
+            .finish()
+    }
+}
+
+/// A pending authorization, the user has to visit [`AuthorizationRequest::url`]
+/// to grant access.
+#[derive(Clone, Debug)]
+pub struct AuthorizationRequest {
+    url: Url,
+    state: String,
+    pkce: Pkce,
+    scopes: Vec<Scope>,
+}
+
+impl AuthorizationRequest {
+    /// The URL the user has to be sent to.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// The state which has to be returned with the authorization code.
+    pub fn state(&self) -> &str {
+        &self.state
+    }
+
+    pub fn pkce(&self) -> &Pkce {
+        &self.pkce
+    }
+
+    pub fn scopes(&self) -> &[Scope] {
+        &self.scopes
+    }
+}
+
+/// Access (and refresh) token returned by the token endpoint.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TokenSet {
+    pub access_token: String,
+    pub token_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub scopes: Vec<Scope>,
+    /// Account name the token was issued for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// Account uuid the token was issued for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+}
+
+impl TokenSet {
+    /// Creates a token set from a bare access token which never expires.
+    pub fn new(access_token: impl Into<String>) -> Self {
+        Self {
+            access_token: access_token.into(),
+            token_type: "bearer".to_string(),
+            expires_at: None,
+            refresh_token: None,
+            scopes: Vec::new(),
+            username: None,
+            sub: None,
+        }
+    }
+
+    /// Whether the access token is expired or expires within the next `leeway`.
+    pub fn expires_within(&self, leeway: chrono::Duration) -> bool {
+        self.expires_at
+            .map(|expires_at| expires_at <= Utc::now() + leeway)
+            .unwrap_or(false)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_within(chrono::Duration::zero())
+    }
+}
+
+impl fmt::Debug for TokenSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenSet")
+            .field("access_token", &"<redacted>")
+            .field("token_type", &self.token_type)
+            .field("expires_at", &self.expires_at)
+            .field(
+                "refresh_token",
+                &self.refresh_token.as_ref().map(|_| "<redacted>"),
+            )
+            .field("scopes", &self.scopes)
+            .field("username", &self.username)
+            .field("sub", &self.sub)
+            .finish()
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: String,
+    #[serde(default)]
+    expires_in: Option<i64>,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    scope: Option<String>,
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    sub: Option<String>,
+}
+
+impl From<TokenResponse> for TokenSet {
+    fn from(response: TokenResponse) -> Self {
+        Self {
+            access_token: response.access_token,
+            token_type: response.token_type,
+            expires_at: response
+                .expires_in
+                .map(|expires_in| Utc::now() + chrono::Duration::seconds(expires_in)),
+            refresh_token: response.refresh_token,
+            scopes: response
+                .scope
+                .map(|scope| scope.split_whitespace().map(Scope::from).collect())
+                .unwrap_or_default(),
+            username: response.username,
+            sub: response.sub,
+        }
+    }
+}
+
+/// Error returned by the authorization server.
+#[derive(Clone, Debug, Deserialize)]
+pub struct OAuthError {
+    pub error: String,
+    #[serde(default)]
+    pub error_description: Option<String>,
+}
+
+impl fmt::Display for OAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.error)?;
+        if let Some(description) = self.error_description.as_ref() {
+            write!(f, ": {description}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Client for the Path of Exile authorization server.
+#[derive(Clone)]
+pub struct OAuthClient {
+    transport: Arc<dyn Transport>,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_uri: Option<String>,
+    authorize_url: Url,
+    token_url: Url,
+    user_agent: String,
+}
+
+impl OAuthClient {
+    /// Creates a new client for a public (PKCE only) OAuth client.
+    pub fn new(client_id: impl Into<String>, redirect_uri: impl Into<String>) -> Self {
+        Self {
+            transport: default_transport(),
+            client_id: client_id.into(),
+            client_secret: None,
+            redirect_uri: Some(redirect_uri.into()),
+            authorize_url: Url::parse(AUTHORIZE_URL).unwrap(),
+            token_url: Url::parse(TOKEN_URL).unwrap(),
+            user_agent: format!("poe-rs/{}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+
+    /// Creates a new client for a confidential OAuth client, which can use
+    /// the client credentials grant to access service scopes.
+    pub fn confidential(client_id: impl Into<String>, client_secret: impl Into<String>) -> Self {
+        Self {
+            transport: default_transport(),
+            client_id: client_id.into(),
+            client_secret: Some(client_secret.into()),
+            redirect_uri: None,
+            authorize_url: Url::parse(AUTHORIZE_URL).unwrap(),
+            token_url: Url::parse(TOKEN_URL).unwrap(),
+            user_agent: format!("poe-rs/{}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+
+    /// Sets the client secret of a confidential client.
+    pub fn client_secret(mut self, client_secret: impl Into<String>) -> Self {
+        self.client_secret = Some(client_secret.into());
+        self
+    }
+
+    /// Sets the redirect URI used for the authorization code grant.
+    pub fn redirect_uri(mut self, redirect_uri: impl Into<String>) -> Self {
+        self.redirect_uri = Some(redirect_uri.into());
+        self
+    }
+
+    /// Overrides the authorization endpoint.
+    pub fn authorize_url(mut self, url: Url) -> Self {
+        self.authorize_url = url;
+        self
+    }
+
+    /// Overrides the token endpoint.
+    pub fn token_url(mut self, url: Url) -> Self {
+        self.token_url = url;
+        self
+    }
+
+    /// Sets the transport used for requests to the token endpoint.
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Sets the User-Agent used for requests to the token endpoint.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Starts a new authorization with a fresh PKCE verifier and state.
+    pub fn authorize(&self, scopes: &[Scope]) -> AuthorizationRequest {
+        let pkce = Pkce::new();
+        let state = random_string(16);
+
+        let mut url = self.authorize_url.clone();
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("client_id", &self.client_id)
+                .append_pair("response_type", "code")
+                .append_pair("scope", &join_scopes(scopes))
+                .append_pair("state", &state);
+            if let Some(redirect_uri) = self.redirect_uri.as_ref() {
+                query.append_pair("redirect_uri", redirect_uri);
+            }
+            query
+                .append_pair("code_challenge", pkce.challenge())
+                .append_pair("code_challenge_method", "S256");
+        }
+
+        AuthorizationRequest {
+            url,
+            state,
+            pkce,
+            scopes: scopes.to_vec(),
+        }
+    }
+
+    /// Exchanges the authorization code received on the redirect URI for a token.
+    pub async fn exchange_code(
+        &self,
+        request: &AuthorizationRequest,
+        code: &str,
+    ) -> PoeResult<TokenSet> {
+        let scopes = join_scopes(&request.scopes);
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("scope", scopes.as_str()),
+            ("code_verifier", request.pkce.verifier()),
+        ];
+        if let Some(redirect_uri) = self.redirect_uri.as_ref() {
+            form.push(("redirect_uri", redirect_uri));
+        }
+
+        self.token(form).await
+    }
+
+    /// Requests a token for the given service scopes with the client credentials grant.
+    ///
+    /// Only available to confidential clients.
+    pub async fn client_credentials(&self, scopes: &[Scope]) -> PoeResult<TokenSet> {
+        let scopes = join_scopes(scopes);
+
+        self.token(vec![
+            ("grant_type", "client_credentials"),
+            ("scope", scopes.as_str()),
+        ])
+        .await
+    }
+
+    /// Requests a new access token with a refresh token.
+    ///
+    /// The refresh token is rotated, the returned token set contains the new refresh token.
+    pub async fn refresh(&self, refresh_token: &str) -> PoeResult<TokenSet> {
+        self.token(vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+        ])
+        .await
+    }
+
+    async fn token(&self, mut form: Vec<(&str, &str)>) -> PoeResult<TokenSet> {
+        form.push(("client_id", &self.client_id));
+        if let Some(client_secret) = self.client_secret.as_ref() {
+            form.push(("client_secret", client_secret));
+        }
+
+        let body = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(form)
+            .finish();
+
+        let request = http::Request::post(self.token_url.as_str())
+            .header(USER_AGENT, &self.user_agent)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(body.into_bytes())
+            .map_err(|err| PoeError::Transport(Box::new(err)))?;
+
+        let response = self.transport.send(request).await?;
+
+        if response.status().is_success() {
+            let token = serde_json::from_slice::<TokenResponse>(response.body())?;
+            return Ok(token.into());
+        }
+
+        match serde_json::from_slice::<OAuthError>(response.body()) {
+            Ok(error) => Err(PoeError::OAuth(error)),
+            Err(error) => Err(PoeError::from(error)),
+        }
+    }
+}
+
+impl fmt::Debug for OAuthClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OAuthClient")
+            .field("client_id", &self.client_id)
+            .field(
+                "client_secret",
+                &self.client_secret.as_ref().map(|_| "<redacted>"),
+            )
+            .field("redirect_uri", &self.redirect_uri)
+            .field("authorize_url", &self.authorize_url)
+            .field("token_url", &self.token_url)
+            .field("user_agent", &self.user_agent)
+            .finish()
+    }
+}
+
+/// Persistent storage for OAuth tokens, used to keep refreshed tokens across restarts.
+pub trait TokenStore: Send + Sync {
+    /// Loads the stored token, `None` if no token has been stored yet.
+    fn load(&self) -> BoxFuture<'_, PoeResult<Option<TokenSet>>>;
+
+    /// Replaces the stored token.
+    fn store<'a>(&'a self, token: &'a TokenSet) -> BoxFuture<'a, PoeResult<()>>;
+}
+
+impl<T: TokenStore + ?Sized> TokenStore for std::sync::Arc<T> {
+    fn load(&self) -> BoxFuture<'_, PoeResult<Option<TokenSet>>> {
+        (**self).load()
+    }
+
+    fn store<'a>(&'a self, token: &'a TokenSet) -> BoxFuture<'a, PoeResult<()>> {
+        (**self).store(token)
+    }
+}
+
+/// A [`TokenStore`] which only keeps the token in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryTokenStore {
+    token: Mutex<Option<TokenSet>>,
+}
+
+impl InMemoryTokenStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a store which already contains a token.
+    pub fn with_token(token: TokenSet) -> Self {
+        Self {
+            token: Mutex::new(Some(token)),
+        }
+    }
+
+    /// Returns the currently stored token.
+    pub fn get(&self) -> Option<TokenSet> {
+        self.token.lock().unwrap().clone()
+    }
+}
+
+impl TokenStore for InMemoryTokenStore {
+    fn load(&self) -> BoxFuture<'_, PoeResult<Option<TokenSet>>> {
+        Box::pin(async move { Ok(self.get()) })
+    }
+
+    fn store<'a>(&'a self, token: &'a TokenSet) -> BoxFuture<'a, PoeResult<()>> {
+        Box::pin(async move {
+            *self.token.lock().unwrap() = Some(token.clone());
+            Ok(())
+        })
+    }
+}
+
+/// A [`TokenStore`] which keeps the token as JSON in a file.
+#[derive(Debug, Clone)]
+pub struct JsonFileTokenStore {
+    path: PathBuf,
+}
+
+impl JsonFileTokenStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl TokenStore for JsonFileTokenStore {
+    fn load(&self) -> BoxFuture<'_, PoeResult<Option<TokenSet>>> {
+        Box::pin(async move {
+            match tokio::fs::read(&self.path).await {
+                Ok(content) => Ok(Some(serde_json::from_slice(&content)?)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        })
+    }
+
+    fn store<'a>(&'a self, token: &'a TokenSet) -> BoxFuture<'a, PoeResult<()>> {
+        Box::pin(async move {
+            let content = serde_json::to_vec_pretty(token)?;
+
+            // the refresh token grants access to the account, only the owner may read it
+            write_file_atomic(&self.path, &content, true).await?;
+
+            Ok(())
+        })
+    }
+}
+
+/// Receives the authorization code on a local loopback redirect URI,
+/// useful for CLI and desktop applications.
+#[derive(Debug)]
+pub struct LoopbackRedirect {
+    listener: TcpListener,
+    redirect_uri: String,
+}
+
+impl LoopbackRedirect {
+    /// Listens on `127.0.0.1` on the given port, `0` picks a random free port.
+    pub async fn bind(port: u16) -> PoeResult<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+        let port = listener.local_addr()?.port();
+
+        Ok(Self {
+            listener,
+            redirect_uri: format!("http://127.0.0.1:{port}/callback"),
+        })
+    }
+
+    /// The redirect URI which has to be used for the authorization.
+    pub fn redirect_uri(&self) -> &str {
+        &self.redirect_uri
+    }
+
+    /// Waits for the authorization server to redirect the user back and
+    /// returns the authorization code after validating the state.
+    ///
+    /// Other requests, like speculative connections of the browser or a request
+    /// for `/favicon.ico`, are answered with `404 Not Found` and ignored.
+    pub async fn receive_code(self, state: &str) -> PoeResult<String> {
+        // connections are read concurrently, a browser may keep an idle connection open
+        // and send the redirect over another one
+        let mut requests = FuturesUnordered::new();
+
+        loop {
+            if requests.is_empty() {
+                let (stream, _) = self.listener.accept().await?;
+                requests.push(read_request_line(stream));
+                continue;
+            }
+
+            let accept = self.listener.accept();
+            futures::pin_mut!(accept);
+            let next = match future::select(accept, requests.next()).await {
+                Either::Left((accepted, _)) => Either::Left(accepted),
+                Either::Right((request, _)) => Either::Right(request),
+            };
+
+            let (stream, request_line) = match next {
+                Either::Left(accepted) => {
+                    requests.push(read_request_line(accepted?.0));
+                    continue;
+                }
+                Either::Right(request) => request.expect("requests are not empty"),
+            };
+
+            let result = match request_line
+                .ok()
+                .and_then(|line| parse_redirect(&line, state))
+            {
+                Some(result) => result,
+                None => {
+                    // the browser may already have closed the connection
+                    let _ = respond(stream, "404 Not Found", "Not found.").await;
+                    continue;
+                }
+            };
+
+            let body = match result {
+                Ok(_) => "Authorization successful, you can close this window now.",
+                Err(_) => "Authorization failed, please try again.",
+            };
+            respond(stream, "200 OK", body).await?;
+
+            return result;
+        }
+    }
+}
+
+async fn read_request_line(mut stream: TcpStream) -> (TcpStream, std::io::Result<String>) {
+    let mut request_line = String::new();
+    let result = BufReader::new(&mut stream)
+        .read_line(&mut request_line)
+        .await
+        .map(|_| request_line);
+    (stream, result)
+}
+
+async fn respond(mut stream: TcpStream, status: &str, body: &str) -> std::io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Parses the request line of the redirect, `None` if the request is not the redirect
+/// with either an authorization code or an error.
+fn parse_redirect(request_line: &str, expected_state: &str) -> Option<PoeResult<String>> {
+    let invalid = |description: &str| {
+        PoeError::OAuth(OAuthError {
+            error: "invalid_request".to_string(),
+            error_description: Some(description.to_string()),
+        })
+    };
+
+    let target = request_line.split_whitespace().nth(1)?;
+    let url = Url::parse("http://127.0.0.1").unwrap().join(target).ok()?;
+    if url.path() != "/callback" {
+        return None;
+    }
+
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+
+    if let Some(error) = param("error") {
+        return Some(Err(PoeError::OAuth(OAuthError {
+            error,
+            error_description: param("error_description"),
+        })));
+    }
+
+    let code = param("code")?;
+    if param("state").as_deref() != Some(expected_state) {
+        return Some(Err(invalid("state mismatch")));
+    }
+
+    Some(Ok(code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "reqwest-transport")]
+    use wiremock::matchers::{body_string_contains, header, method, path};
+    #[cfg(feature = "reqwest-transport")]
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[test]
+    fn pkce_challenge() {
+        let pkce = Pkce::from_verifier("dBjftJeZ4CVP-mB92K27uhbUJU22LVSVHM6Z2Rbv9tw");
+        assert_eq!(
+            "6AzuPv4HoSsJaWiBo4S0MbODuGJqAIfojFQ83PllCMk",
+            pkce.challenge()
+        );
+    }
+
+    #[test]
+    fn authorize_url() {
+        let oauth = OAuthClient::new("client", "http://127.0.0.1:1234/callback");
+        let request = oauth.authorize(&[Scope::AccountProfile, Scope::AccountCharacters]);
+
+        let params = request
+            .url()
+            .query_pairs()
+            .into_owned()
+            .collect::<std::collections::HashMap<_, _>>();
+
+        assert_eq!("client", params["client_id"]);
+        assert_eq!("code", params["response_type"]);
+        assert_eq!("account:profile account:characters", params["scope"]);
+        assert_eq!(request.state(), params["state"]);
+        assert_eq!("http://127.0.0.1:1234/callback", params["redirect_uri"]);
+        assert_eq!(request.pkce().challenge(), params["code_challenge"]);
+        assert_eq!("S256", params["code_challenge_method"]);
+    }
+
+    #[test]
+    fn parse_redirect_code() {
+        let line = "GET /callback?code=abc&state=xyz HTTP/1.1\r\n";
+        assert_eq!("abc", parse_redirect(line, "xyz").unwrap().unwrap());
+    }
+
+    #[test]
+    fn parse_redirect_state_mismatch() {
+        let line = "GET /callback?code=abc&state=foo HTTP/1.1\r\n";
+        assert!(matches!(
+            parse_redirect(line, "xyz"),
+            Some(Err(PoeError::OAuth(_)))
+        ));
+    }
+
+    #[test]
+    fn parse_redirect_error() {
+        let line = "GET /callback?error=access_denied&state=xyz HTTP/1.1\r\n";
+        match parse_redirect(line, "xyz") {
+            Some(Err(PoeError::OAuth(error))) => assert_eq!("access_denied", error.error),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_redirect_other_request() {
+        assert!(parse_redirect("", "xyz").is_none());
+        assert!(parse_redirect("GET /favicon.ico HTTP/1.1\r\n", "xyz").is_none());
+        assert!(parse_redirect("GET /callback HTTP/1.1\r\n", "xyz").is_none());
+    }
+
+    #[test]
+    fn scope_roundtrip() {
+        for scope in [
+            "account:profile",
+            "account:guild:stashes",
+            "service:psapi",
+            "service:pvp_matches:ladder",
+        ] {
+            assert_eq!(scope, Scope::from(scope).as_str());
+        }
+        assert_eq!(Scope::ServiceLeagues, Scope::from("service:leagues"));
+        assert_eq!(Scope::Other("foo:bar".to_string()), Scope::from("foo:bar"));
+    }
+
+    #[test]
+    fn oauth_client_debug_is_redacted() {
+        let oauth = OAuthClient::confidential("client", "secret-value");
+        assert!(!format!("{:?}", oauth).contains("secret-value"));
+    }
+
+    #[test]
+    fn token_set_debug_is_redacted() {
+        let mut token = TokenSet::new("secret-access");
+        token.refresh_token = Some("secret-refresh".to_string());
+
+        let debug = format!("{token:?}");
+        assert!(!debug.contains("secret"));
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn exchange_code() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .and(header("User-Agent", "test/1.0"))
+            .and(body_string_contains("grant_type=authorization_code"))
+            .and(body_string_contains("code=the-code"))
+            .and(body_string_contains("code_verifier="))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "access",
+                "expires_in": 36000,
+                "token_type": "bearer",
+                "scope": "account:profile",
+                "username": "Steelmage",
+                "sub": "c5b9c286-8d05-47af-be41-67ab10a8c53e",
+                "refresh_token": "refresh",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let oauth = OAuthClient::new("client", "http://127.0.0.1/callback")
+            .token_url(Url::parse(&format!("{}/oauth/token", server.uri())).unwrap())
+            .user_agent("test/1.0");
+        let request = oauth.authorize(&[Scope::AccountProfile]);
+
+        let token = oauth.exchange_code(&request, "the-code").await.unwrap();
+        assert_eq!("access", token.access_token);
+        assert_eq!(Some("refresh"), token.refresh_token.as_deref());
+        assert_eq!(vec![Scope::AccountProfile], token.scopes);
+        assert_eq!(Some("Steelmage"), token.username.as_deref());
+        assert!(!token.is_expired());
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn exchange_code_error() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({
+                "error": "invalid_grant",
+                "error_description": "authorization code expired",
+            })))
+            .mount(&server)
+            .await;
+
+        let oauth = OAuthClient::new("client", "http://127.0.0.1/callback")
+            .token_url(Url::parse(&server.uri()).unwrap());
+        let request = oauth.authorize(&[Scope::AccountProfile]);
+
+        match oauth.exchange_code(&request, "the-code").await {
+            Err(PoeError::OAuth(error)) => assert_eq!("invalid_grant", error.error),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn client_credentials() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("grant_type=client_credentials"))
+            .and(body_string_contains("client_id=client"))
+            .and(body_string_contains("client_secret=secret"))
+            .and(body_string_contains("scope=service%3Apsapi"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "access",
+                "expires_in": null,
+                "token_type": "bearer",
+                "scope": "service:psapi",
+                "username": "client",
+                "sub": "c5b9c286-8d05-47af-be41-67ab10a8c53e",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let oauth = OAuthClient::confidential("client", "secret")
+            .token_url(Url::parse(&server.uri()).unwrap());
+
+        let token = oauth
+            .client_credentials(&[Scope::ServicePsapi])
+            .await
+            .unwrap();
+        assert_eq!("access", token.access_token);
+        assert_eq!(None, token.expires_at);
+        assert_eq!(vec![Scope::ServicePsapi], token.scopes);
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn refresh() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("grant_type=refresh_token"))
+            .and(body_string_contains("refresh_token=old-refresh"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "access_token": "new-access",
+                "expires_in": 36000,
+                "token_type": "bearer",
+                "refresh_token": "new-refresh",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let oauth = OAuthClient::new("client", "http://127.0.0.1/callback")
+            .token_url(Url::parse(&server.uri()).unwrap());
+
+        let token = oauth.refresh("old-refresh").await.unwrap();
+        assert_eq!("new-access", token.access_token);
+        assert_eq!(Some("new-refresh"), token.refresh_token.as_deref());
+    }
+
+    #[tokio::test]
+    async fn in_memory_token_store() {
+        let store = InMemoryTokenStore::new();
+        assert!(store.load().await.unwrap().is_none());
+
+        store.store(&TokenSet::new("access")).await.unwrap();
+        assert_eq!("access", store.load().await.unwrap().unwrap().access_token);
+    }
+
+    #[tokio::test]
+    async fn json_file_token_store() {
+        let path = std::env::temp_dir().join(format!("poe-rs-token-{}.json", random_string(8)));
+        let store = JsonFileTokenStore::new(&path);
+        assert!(store.load().await.unwrap().is_none());
+
+        let mut token = TokenSet::new("access");
+        token.refresh_token = Some("refresh".to_string());
+        token.expires_at = Some(Utc::now());
+        token.scopes = vec![Scope::AccountProfile];
+        store.store(&token).await.unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(0o600, mode & 0o777);
+        }
+
+        let loaded = store.load().await.unwrap().unwrap();
+        assert_eq!("access", loaded.access_token);
+        assert_eq!(Some("refresh"), loaded.refresh_token.as_deref());
+        assert_eq!(token.expires_at, loaded.expires_at);
+        assert_eq!(vec![Scope::AccountProfile], loaded.scopes);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn loopback_redirect() {
+        let redirect = LoopbackRedirect::bind(0).await.unwrap();
+        let url = format!("{}?code=the-code&state=the-state", redirect.redirect_uri());
+
+        let browser = tokio::spawn(async move { reqwest::get(url).await.unwrap().status() });
+
+        let code = redirect.receive_code("the-state").await.unwrap();
+        assert_eq!("the-code", code);
+        assert!(browser.await.unwrap().is_success());
+    }
+
+    #[cfg(feature = "reqwest-transport")]
+    #[tokio::test]
+    async fn loopback_redirect_ignores_other_requests() {
+        let redirect = LoopbackRedirect::bind(0).await.unwrap();
+        let addr = redirect.redirect_uri()["http://".len()..]
+            .split('/')
+            .next()
+            .unwrap()
+            .to_string();
+        let favicon = format!("http://{addr}/favicon.ico");
+        let url = format!("{}?code=the-code&state=the-state", redirect.redirect_uri());
+
+        let browser = tokio::spawn(async move {
+            // a speculative connection which never sends a request
+            let _preconnect = tokio::net::TcpStream::connect(&addr).await.unwrap();
+            let favicon = reqwest::get(favicon).await.unwrap().status();
+            (favicon, reqwest::get(url).await.unwrap().status())
+        });
+
+        let code = redirect.receive_code("the-state").await.unwrap();
+        assert_eq!("the-code", code);
+
+        let (favicon, callback) = browser.await.unwrap();
+        assert_eq!(reqwest::StatusCode::NOT_FOUND, favicon);
+        assert!(callback.is_success());
+    }
+}
@@ -4,6 +4,7 @@ use futures::{ready, Stream, StreamExt};
 use std::future::Future;
 use std::iter::Iterator;
 use std::pin::Pin;
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct PageRequest {
@@ -100,6 +101,42 @@ where
     }
 }
 
+/// A page of a cursor based endpoint, e.g. the public stash tab river.
+#[derive(Debug, Clone)]
+pub struct CursorPage<T, C> {
+    pub value: T,
+    /// Cursor to request the next page with.
+    pub next: C,
+    /// Whether the page was the most recent one, the next page is requested after a delay.
+    pub caught_up: bool,
+}
+
+/// Creates a stream which follows a cursor forever, starting at `cursor`.
+///
+/// Once a page is caught up, the next page is requested after `backoff`.
+/// The stream ends after the first error, it can be resumed with the last received cursor.
+pub fn cursor_stream<F, Fut, T, C, E>(
+    cursor: C,
+    backoff: Duration,
+    fetch: F,
+) -> impl Stream<Item = Result<T, E>>
+where
+    F: FnMut(C) -> Fut,
+    Fut: Future<Output = Result<CursorPage<T, C>, E>>,
+{
+    futures::stream::unfold(Some((cursor, false, fetch)), move |state| async move {
+        let (cursor, caught_up, mut fetch) = state?;
+        if caught_up {
+            tokio::time::sleep(backoff).await;
+        }
+
+        match fetch(cursor).await {
+            Ok(page) => Some((Ok(page.value), Some((page.next, page.caught_up, fetch)))),
+            Err(err) => Some((Err(err), None)),
+        }
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -138,4 +175,27 @@ mod tests {
         assert_eq!(Err("error"), ps.try_next().await);
         assert_eq!(None, ps.try_next().await.unwrap());
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn cursor() {
+        let start = tokio::time::Instant::now();
+        let stream = cursor_stream(0, Duration::from_secs(5), |cursor| async move {
+            if cursor > 3 {
+                return Err("error");
+            }
+            Ok(CursorPage {
+                value: (cursor, start.elapsed().as_secs()),
+                next: cursor + 1,
+                caught_up: cursor == 1,
+            })
+        });
+        futures::pin_mut!(stream);
+
+        assert_eq!(Some(Ok((0, 0))), stream.next().await);
+        assert_eq!(Some(Ok((1, 0))), stream.next().await);
+        assert_eq!(Some(Ok((2, 5))), stream.next().await);
+        assert_eq!(Some(Ok((3, 5))), stream.next().await);
+        assert_eq!(Some(Err("error")), stream.next().await);
+        assert_eq!(None, stream.next().await);
+    }
 }
@@ -0,0 +1,268 @@
+use crate::api::{LadderSort, LeagueType, PvpMatchType, Realm};
+
+/// Query parameters to list leagues with [`crate::PathOfExile::leagues_with`].
+#[derive(Clone, Debug)]
+pub struct LeaguesQuery {
+    realm: Option<Realm>,
+    league_type: Option<LeagueType>,
+    season: Option<String>,
+    limit: usize,
+    offset: usize,
+}
+
+impl Default for LeaguesQuery {
+    fn default() -> Self {
+        Self {
+            realm: None,
+            league_type: None,
+            season: None,
+            limit: 50,
+            offset: 0,
+        }
+    }
+}
+
+impl LeaguesQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only lists leagues of the realm. Defaults to the realm of the client.
+    pub fn realm(mut self, realm: Realm) -> Self {
+        self.realm = Some(realm);
+        self
+    }
+
+    /// Only lists leagues of the type. Defaults to [`LeagueType::Main`].
+    pub fn league_type(mut self, league_type: LeagueType) -> Self {
+        self.league_type = Some(league_type);
+        self
+    }
+
+    /// Only lists leagues of a season, implies [`LeagueType::Season`].
+    pub fn season(mut self, season: impl Into<String>) -> Self {
+        self.league_type = Some(LeagueType::Season);
+        self.season = Some(season.into());
+        self
+    }
+
+    /// Sets the maximum number of leagues to list. Defaults to `50`.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub(crate) fn to_query_string(&self, default_realm: &Realm) -> String {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        query.append_pair(
+            "realm",
+            self.realm.as_ref().unwrap_or(default_realm).as_str(),
+        );
+        if let Some(league_type) = self.league_type {
+            query.append_pair("type", league_type.as_str());
+        }
+        if let Some(season) = self.season.as_ref() {
+            query.append_pair("season", season);
+        }
+        query.append_pair("limit", &self.limit.to_string());
+        query.append_pair("offset", &self.offset.to_string());
+        query.finish()
+    }
+}
+
+/// Query parameters to request a ladder with [`crate::PathOfExile::league_ladder`].
+#[derive(Clone, Debug)]
+pub struct LadderQuery {
+    realm: Option<Realm>,
+    sort: Option<LadderSort>,
+    class: Option<String>,
+    limit: usize,
+    offset: usize,
+}
+
+impl Default for LadderQuery {
+    fn default() -> Self {
+        Self {
+            realm: None,
+            sort: None,
+            class: None,
+            limit: 200,
+            offset: 0,
+        }
+    }
+}
+
+impl LadderQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the realm of the league. Defaults to the realm of the client.
+    pub fn realm(mut self, realm: Realm) -> Self {
+        self.realm = Some(realm);
+        self
+    }
+
+    /// Sets the order of the ladder. Defaults to [`LadderSort::Xp`].
+    pub fn sort(mut self, sort: LadderSort) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    /// Only includes characters of a class, implies [`LadderSort::Class`].
+    pub fn class(mut self, class: impl Into<String>) -> Self {
+        self.sort = Some(LadderSort::Class);
+        self.class = Some(class.into());
+        self
+    }
+
+    /// Sets the maximum number of entries. Defaults to `200`, the maximum allowed by the API.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub(crate) fn to_query_string(&self, default_realm: &Realm) -> String {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        query.append_pair(
+            "realm",
+            self.realm.as_ref().unwrap_or(default_realm).as_str(),
+        );
+        if let Some(sort) = self.sort {
+            query.append_pair("sort", sort.as_str());
+        }
+        if let Some(class) = self.class.as_ref() {
+            query.append_pair("class", class);
+        }
+        query.append_pair("limit", &self.limit.to_string());
+        query.append_pair("offset", &self.offset.to_string());
+        query.finish()
+    }
+}
+
+/// Query parameters to list PvP matches with [`crate::PathOfExile::pvp_matches`].
+#[derive(Clone, Debug, Default)]
+pub struct PvpMatchesQuery {
+    realm: Option<Realm>,
+    match_type: Option<PvpMatchType>,
+    season: Option<String>,
+    league: Option<String>,
+}
+
+impl PvpMatchesQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only lists matches of the realm. Defaults to the realm of the client.
+    pub fn realm(mut self, realm: Realm) -> Self {
+        self.realm = Some(realm);
+        self
+    }
+
+    /// Only lists matches of the type. Defaults to [`PvpMatchType::Upcoming`].
+    pub fn match_type(mut self, match_type: PvpMatchType) -> Self {
+        self.match_type = Some(match_type);
+        self
+    }
+
+    /// Only lists matches of a season, implies [`PvpMatchType::Season`].
+    pub fn season(mut self, season: impl Into<String>) -> Self {
+        self.match_type = Some(PvpMatchType::Season);
+        self.season = Some(season.into());
+        self
+    }
+
+    /// Only lists matches of a league, implies [`PvpMatchType::League`].
+    pub fn league(mut self, league: impl Into<String>) -> Self {
+        self.match_type = Some(PvpMatchType::League);
+        self.league = Some(league.into());
+        self
+    }
+
+    pub(crate) fn to_query_string(&self, default_realm: &Realm) -> String {
+        let mut query = form_urlencoded::Serializer::new(String::new());
+        query.append_pair(
+            "realm",
+            self.realm.as_ref().unwrap_or(default_realm).as_str(),
+        );
+        if let Some(match_type) = self.match_type {
+            query.append_pair("type", match_type.as_str());
+        }
+        if let Some(season) = self.season.as_ref() {
+            query.append_pair("season", season);
+        }
+        if let Some(league) = self.league.as_ref() {
+            query.append_pair("league", league);
+        }
+        query.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leagues_query() {
+        assert_eq!(
+            "realm=pc&limit=50&offset=0",
+            LeaguesQuery::new().to_query_string(&Realm::Pc)
+        );
+        assert_eq!(
+            "realm=xbox&type=season&season=Race+Season+1&limit=10&offset=20",
+            LeaguesQuery::new()
+                .realm(Realm::Xbox)
+                .season("Race Season 1")
+                .limit(10)
+                .offset(20)
+                .to_query_string(&Realm::Pc)
+        );
+    }
+
+    #[test]
+    fn ladder_query() {
+        assert_eq!(
+            "realm=pc&limit=200&offset=0",
+            LadderQuery::new().to_query_string(&Realm::Pc)
+        );
+        assert_eq!(
+            "realm=sony&sort=depthsolo&limit=10&offset=0",
+            LadderQuery::new()
+                .realm(Realm::Sony)
+                .sort(LadderSort::DepthSolo)
+                .limit(10)
+                .to_query_string(&Realm::Pc)
+        );
+        assert_eq!(
+            "realm=pc&sort=class&class=Necromancer&limit=200&offset=0",
+            LadderQuery::new()
+                .class("Necromancer")
+                .to_query_string(&Realm::Pc)
+        );
+    }
+
+    #[test]
+    fn pvp_matches_query() {
+        assert_eq!(
+            "realm=xbox",
+            PvpMatchesQuery::new().to_query_string(&Realm::Xbox)
+        );
+        assert_eq!(
+            "realm=pc&type=league&league=Hardcore",
+            PvpMatchesQuery::new()
+                .league("Hardcore")
+                .to_query_string(&Realm::Pc)
+        );
+    }
+}
@@ -0,0 +1,578 @@
+use http::{HeaderMap, StatusCode};
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant};
+
+use crate::response::PoeResult;
+use crate::transport::Response;
+
+const POLICY_HEADER: &str = "x-rate-limit-policy";
+const RULES_HEADER: &str = "x-rate-limit-rules";
+const RETRY_AFTER_HEADER: &str = "retry-after";
+
+/// Time to wait after being rate limited, if the API did not tell us how long to wait.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Limit used for a bucket until the API told us the real limits.
+const DEFAULT_LIMIT: Limit = Limit {
+    max_hits: 5,
+    period: Duration::from_secs(5),
+    penalty: Duration::from_secs(0),
+};
+
+/// A single `limit:period:penalty` triple of a rate limit rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Limit {
+    pub max_hits: u32,
+    pub period: Duration,
+    pub penalty: Duration,
+}
+
+/// A single `hits:period:restricted` triple of a rate limit rule state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct LimitState {
+    pub hits: u32,
+    pub period: Duration,
+    pub restricted: Duration,
+}
+
+fn parse_triple(s: &str) -> Option<(u32, u64, u64)> {
+    let mut parts = s.trim().split(':').map(|part| part.parse::<u64>().ok());
+
+    let first = parts.next()??;
+    let second = parts.next()??;
+    let third = parts.next()??;
+
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some((u32::try_from(first).ok()?, second, third))
+}
+
+impl FromStr for Limit {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (max_hits, period, penalty) = parse_triple(s).ok_or(())?;
+        Ok(Self {
+            max_hits,
+            period: Duration::from_secs(period),
+            penalty: Duration::from_secs(penalty),
+        })
+    }
+}
+
+impl FromStr for LimitState {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (hits, period, restricted) = parse_triple(s).ok_or(())?;
+        Ok(Self {
+            hits,
+            period: Duration::from_secs(period),
+            restricted: Duration::from_secs(restricted),
+        })
+    }
+}
+
+/// A rate limit rule (e.g. `Ip`, `Account` or `Client`) with all of its windows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Rule {
+    pub name: String,
+    pub limits: Vec<Limit>,
+    pub state: Vec<LimitState>,
+}
+
+/// Rate limit information reported by the API through the `X-Rate-Limit-*` headers.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct RateLimitHeaders {
+    pub policy: Option<String>,
+    pub rules: Vec<Rule>,
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn parse_list<T: FromStr>(value: Option<&str>) -> Vec<T> {
+    value
+        .map(|value| {
+            value
+                .split(',')
+                .filter_map(|entry| entry.parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl RateLimitHeaders {
+    /// Extracts the rate limit information from the response headers,
+    /// returns `None` if the response did not contain any.
+    pub(crate) fn parse(headers: &HeaderMap) -> Option<Self> {
+        let policy = header(headers, POLICY_HEADER).map(|policy| policy.trim().to_string());
+
+        let rules = header(headers, RULES_HEADER)
+            .map(|rules| {
+                rules
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(|name| {
+                        let limit_header = format!("x-rate-limit-{}", name.to_ascii_lowercase());
+                        let state_header = format!("{limit_header}-state");
+
+                        Rule {
+                            name: name.to_string(),
+                            limits: parse_list(header(headers, &limit_header)),
+                            state: parse_list(header(headers, &state_header)),
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        if policy.is_none() && rules.is_empty() {
+            return None;
+        }
+
+        Some(Self { policy, rules })
+    }
+}
+
+/// Determines how long to wait before retrying a rate limited (HTTP 429) request.
+///
+/// Prefers the `Retry-After` header and falls back to the restriction time
+/// reported in the `X-Rate-Limit-{rule}-State` headers.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Duration {
+    let retry_after = header(headers, RETRY_AFTER_HEADER)
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs);
+
+    let restricted = RateLimitHeaders::parse(headers).and_then(|headers| {
+        headers
+            .rules
+            .iter()
+            .flat_map(|rule| rule.state.iter())
+            .map(|state| state.restricted)
+            .filter(|restricted| !restricted.is_zero())
+            .max()
+    });
+
+    retry_after.max(restricted).unwrap_or(DEFAULT_RETRY_AFTER)
+}
+
+#[derive(Debug)]
+struct Window {
+    rule: String,
+    limit: Limit,
+    hits: VecDeque<Instant>,
+}
+
+impl Window {
+    fn new(rule: impl Into<String>, limit: Limit) -> Self {
+        Self {
+            rule: rule.into(),
+            limit,
+            hits: VecDeque::new(),
+        }
+    }
+
+    /// Drops all hits which are no longer relevant for this window.
+    fn prune(&mut self, now: Instant) {
+        while let Some(hit) = self.hits.front() {
+            if *hit + self.limit.period <= now {
+                self.hits.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Returns the instant at which the next request fits into this window.
+    fn available_at(&self, now: Instant) -> Instant {
+        let max_hits = self.limit.max_hits as usize;
+
+        if self.hits.len() < max_hits {
+            return now;
+        }
+
+        match self.hits.get(self.hits.len() - max_hits) {
+            Some(hit) => *hit + self.limit.period,
+            // a limit of 0 hits, nothing we can do except waiting a full period
+            None => now + self.limit.period,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BucketState {
+    windows: Vec<Window>,
+    restricted_until: Option<Instant>,
+}
+
+/// Tracks all rate limit windows of a single rate limited resource.
+#[derive(Debug)]
+struct Bucket {
+    state: Mutex<BucketState>,
+}
+
+impl Bucket {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(BucketState {
+                windows: vec![Window::new("default", DEFAULT_LIMIT)],
+                restricted_until: None,
+            }),
+        }
+    }
+
+    /// Waits until a request can be made without exceeding any of the known limits
+    /// and reserves a hit in every window.
+    async fn wait(&self) {
+        loop {
+            let wait_until = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+
+                let mut wait_until = state.restricted_until.filter(|until| *until > now);
+                for window in state.windows.iter_mut() {
+                    window.prune(now);
+                    let available_at = window.available_at(now);
+                    if available_at > now {
+                        wait_until = wait_until.max(Some(available_at));
+                    }
+                }
+
+                match wait_until {
+                    Some(wait_until) => wait_until,
+                    None => {
+                        for window in state.windows.iter_mut() {
+                            window.hits.push_back(now);
+                        }
+                        return;
+                    }
+                }
+            };
+
+            sleep_until(wait_until).await;
+        }
+    }
+
+    /// Blocks all requests for the given duration.
+    fn restrict(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+
+        let mut state = self.state.lock().unwrap();
+        state.restricted_until = state.restricted_until.max(Some(until));
+    }
+
+    /// Replaces the known limits with the limits reported by the API and
+    /// synchronizes the local state with the state reported by the API.
+    fn update(&self, headers: &RateLimitHeaders) {
+        if headers.rules.is_empty() {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        let mut old_windows = std::mem::take(&mut state.windows);
+        for rule in headers.rules.iter() {
+            for limit in rule.limits.iter() {
+                let existing = old_windows
+                    .iter()
+                    .position(|w| w.rule == rule.name && w.limit.period == limit.period);
+
+                let mut window = match existing {
+                    Some(index) => {
+                        let mut window = old_windows.swap_remove(index);
+                        window.limit = *limit;
+                        window
+                    }
+                    None => Window::new(&rule.name, *limit),
+                };
+
+                window.prune(now);
+
+                if let Some(limit_state) = rule.state.iter().find(|s| s.period == limit.period) {
+                    // the API saw more requests than we know of (e.g. from another
+                    // process sharing the same IP), account for them
+                    while window.hits.len() < limit_state.hits as usize {
+                        window.hits.push_back(now);
+                    }
+
+                    if !limit_state.restricted.is_zero() {
+                        let until = now + limit_state.restricted;
+                        state.restricted_until = state.restricted_until.max(Some(until));
+                    }
+                }
+
+                state.windows.push(window);
+            }
+        }
+
+        if state.windows.is_empty() {
+            state.windows = old_windows;
+        }
+    }
+}
+
+/// Identifies the bucket a request is rate limited with.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum BucketKey {
+    /// A call which did not report its rate limit policy yet.
+    Call(String),
+    /// A rate limit policy shared by all calls which reported it.
+    Policy(String),
+}
+
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    policies: Mutex<HashMap<String, String>>,
+    buckets: Mutex<HashMap<BucketKey, Arc<Bucket>>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new() -> Self {
+        Self {
+            policies: Mutex::new(HashMap::new()),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn bucket(&self, call_id: &str) -> Arc<Bucket> {
+        let key = match self.policies.lock().unwrap().get(call_id) {
+            Some(policy) => BucketKey::Policy(policy.clone()),
+            None => BucketKey::Call(call_id.to_string()),
+        };
+
+        self.buckets
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_insert_with(|| Arc::new(Bucket::new()))
+            .clone()
+    }
+
+    /// Remembers the policy reported for a call, all following requests of this call
+    /// share the bucket of the policy.
+    fn assign_policy(&self, call_id: &str, policy: &str) -> Arc<Bucket> {
+        self.policies
+            .lock()
+            .unwrap()
+            .insert(call_id.to_string(), policy.to_string());
+
+        self.bucket(call_id)
+    }
+
+    pub(crate) async fn rate_limited(
+        &self,
+        call_id: &str,
+        api_call: impl Future<Output = PoeResult<Response>>,
+    ) -> PoeResult<Response> {
+        let mut bucket = self.bucket(call_id);
+        bucket.wait().await;
+
+        let response = api_call.await?;
+        if let Some(headers) = RateLimitHeaders::parse(response.headers()) {
+            if let Some(policy) = headers.policy.as_deref() {
+                bucket = self.assign_policy(call_id, policy);
+            }
+            bucket.update(&headers);
+        }
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            bucket.restrict(retry_after(response.headers()));
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+
+    fn headers(headers: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(*name, HeaderValue::from_static(value));
+        }
+        map
+    }
+
+    fn limit(max_hits: u32, period: u64, penalty: u64) -> Limit {
+        Limit {
+            max_hits,
+            period: Duration::from_secs(period),
+            penalty: Duration::from_secs(penalty),
+        }
+    }
+
+    fn state(hits: u32, period: u64, restricted: u64) -> LimitState {
+        LimitState {
+            hits,
+            period: Duration::from_secs(period),
+            restricted: Duration::from_secs(restricted),
+        }
+    }
+
+    #[test]
+    fn parse_triple_invalid() {
+        assert!("1:2".parse::<Limit>().is_err());
+        assert!("1:2:3:4".parse::<Limit>().is_err());
+        assert!("a:2:3".parse::<Limit>().is_err());
+        assert_eq!(Ok(limit(1, 2, 3)), " 1:2:3 ".parse());
+    }
+
+    #[test]
+    fn parse_headers() {
+        let headers = headers(&[
+            ("X-Rate-Limit-Policy", "ladder-view"),
+            ("X-Rate-Limit-Rules", "Ip,Account"),
+            ("X-Rate-Limit-Ip", "10:5:10,30:60:300"),
+            ("X-Rate-Limit-Ip-State", "1:5:0,1:60:0"),
+            ("X-Rate-Limit-Account", "3:5:60"),
+            ("X-Rate-Limit-Account-State", "3:5:42"),
+        ]);
+
+        let parsed = RateLimitHeaders::parse(&headers).unwrap();
+        assert_eq!(Some("ladder-view"), parsed.policy.as_deref());
+        assert_eq!(
+            vec![
+                Rule {
+                    name: "Ip".to_string(),
+                    limits: vec![limit(10, 5, 10), limit(30, 60, 300)],
+                    state: vec![state(1, 5, 0), state(1, 60, 0)],
+                },
+                Rule {
+                    name: "Account".to_string(),
+                    limits: vec![limit(3, 5, 60)],
+                    state: vec![state(3, 5, 42)],
+                },
+            ],
+            parsed.rules
+        );
+    }
+
+    #[test]
+    fn parse_headers_missing() {
+        assert_eq!(None, RateLimitHeaders::parse(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn retry_after_header() {
+        let headers = headers(&[
+            ("Retry-After", "30"),
+            ("X-Rate-Limit-Rules", "Ip"),
+            ("X-Rate-Limit-Ip", "10:5:10"),
+            ("X-Rate-Limit-Ip-State", "11:5:10"),
+        ]);
+        assert_eq!(Duration::from_secs(30), retry_after(&headers));
+    }
+
+    #[test]
+    fn retry_after_restricted() {
+        let headers = headers(&[
+            ("X-Rate-Limit-Rules", "Ip,Account"),
+            ("X-Rate-Limit-Ip", "10:5:10,30:60:300"),
+            ("X-Rate-Limit-Ip-State", "11:5:10,11:60:0"),
+            ("X-Rate-Limit-Account", "3:5:60"),
+            ("X-Rate-Limit-Account-State", "4:5:60"),
+        ]);
+        assert_eq!(Duration::from_secs(60), retry_after(&headers));
+    }
+
+    #[test]
+    fn retry_after_default() {
+        assert_eq!(DEFAULT_RETRY_AFTER, retry_after(&HeaderMap::new()));
+    }
+
+    fn rules(rules: Vec<Rule>) -> RateLimitHeaders {
+        RateLimitHeaders {
+            policy: None,
+            rules,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bucket_enforces_every_window() {
+        let bucket = Bucket::new();
+        bucket.update(&rules(vec![Rule {
+            name: "Ip".to_string(),
+            limits: vec![limit(2, 1, 0), limit(3, 10, 0)],
+            state: vec![],
+        }]));
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            bucket.wait().await;
+        }
+        // the first two fit, the third has to wait for the 1 second window
+        assert_eq!(Duration::from_secs(1), start.elapsed());
+
+        bucket.wait().await;
+        // the fourth has to wait for the 10 second window
+        assert_eq!(Duration::from_secs(10), start.elapsed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bucket_syncs_state() {
+        let bucket = Bucket::new();
+        bucket.update(&rules(vec![Rule {
+            name: "Ip".to_string(),
+            limits: vec![limit(2, 5, 0)],
+            state: vec![state(2, 5, 0)],
+        }]));
+
+        let start = Instant::now();
+        bucket.wait().await;
+        assert_eq!(Duration::from_secs(5), start.elapsed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bucket_restricted() {
+        let bucket = Bucket::new();
+        bucket.update(&rules(vec![Rule {
+            name: "Ip".to_string(),
+            limits: vec![limit(10, 5, 60)],
+            state: vec![state(11, 5, 60)],
+        }]));
+
+        let start = Instant::now();
+        bucket.wait().await;
+        assert_eq!(Duration::from_secs(60), start.elapsed());
+    }
+
+    #[test]
+    fn rate_limiter_shares_policy_buckets() {
+        let limiter = RateLimiter::new();
+
+        let leagues = limiter.bucket("leagues");
+        let ladder = limiter.bucket("ladder");
+        assert!(!Arc::ptr_eq(&leagues, &ladder));
+
+        let leagues = limiter.assign_policy("leagues", "ladder-view");
+        let ladder = limiter.assign_policy("ladder", "ladder-view");
+        assert!(Arc::ptr_eq(&leagues, &ladder));
+        assert!(Arc::ptr_eq(&leagues, &limiter.bucket("leagues")));
+        assert!(Arc::ptr_eq(&ladder, &limiter.bucket("ladder")));
+
+        let other = limiter.bucket("other");
+        assert!(!Arc::ptr_eq(&leagues, &other));
+    }
+
+    #[test]
+    fn rate_limiter_policy_does_not_clash_with_call() {
+        let limiter = RateLimiter::new();
+
+        let call = limiter.bucket("ladder");
+        let policy = limiter.assign_policy("leagues", "ladder");
+        assert!(!Arc::ptr_eq(&call, &policy));
+    }
+}
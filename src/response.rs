@@ -1,16 +1,78 @@
 use serde::Deserialize;
+use std::fmt;
 use std::result::Result;
+use std::time::Duration;
 use thiserror::Error;
 
+use crate::api::{ItemFilter, ItemFilterValidation};
+use crate::oauth::OAuthError;
+
 #[derive(Error, Debug)]
 pub enum PoeError {
-    #[error("the resource is not available")]
+    /// Code `1`: the requested resource does not exist.
+    #[error("the resource is not available: {0}")]
     NotFound(ApiError),
-    #[error("unknown API error")]
+    /// Code `2`: the request contained invalid parameters.
+    #[error("invalid query: {0}")]
+    InvalidQuery(ApiError),
+    /// Code `3`: the rate limit was exceeded.
+    ///
+    /// Requests answered with HTTP 429 are retried and fail with [`PoeError::RateLimited`]
+    /// once the retry budget is exhausted.
+    #[error("rate limit exceeded: {0}")]
+    RateLimitExceeded(ApiError),
+    /// Code `4`: the API failed to process the request.
+    #[error("internal API error: {0}")]
+    InternalError(ApiError),
+    /// Code `5`: the request body had an unexpected content type.
+    #[error("unexpected content type: {0}")]
+    UnexpectedContentType(ApiError),
+    /// Code `6`: access to the resource is not allowed.
+    #[error("forbidden: {0}")]
+    Forbidden(ApiError),
+    /// Code `7`: the API is temporarily unavailable, e.g. during maintenance.
+    #[error("temporarily unavailable: {0}")]
+    TemporarilyUnavailable(ApiError),
+    /// Code `8`: the request requires a valid access token.
+    #[error("unauthorized: {0}")]
+    Unauthorized(ApiError),
+    /// Code `9`: the HTTP method is not allowed for this resource.
+    #[error("method not allowed: {0}")]
+    MethodNotAllowed(ApiError),
+    /// Code `10`: the request was well-formed but could not be processed.
+    #[error("unprocessable entity: {0}")]
+    UnprocessableEntity(ApiError),
+    #[error("unknown API error: {0}")]
     UnknownApiError(ApiError),
-    #[cfg(feature = "client")]
+    #[error("rate limited, retry after {retry_after:?}")]
+    RateLimited { retry_after: Duration },
+    /// The API responded with an error status without an error description.
+    #[error("unexpected HTTP status {status} for {url}")]
+    UnexpectedStatus { status: u16, url: String },
+    #[cfg(feature = "reqwest-transport")]
     #[error("unexpected transport or decoding error occured")]
     Reqwest(#[from] reqwest::Error),
+    /// An item filter was rejected by the validation of the API.
+    ///
+    /// The filter was still created or updated, `filter` is the filter as returned by the API
+    /// without its `validation`.
+    #[error("item filter {} is {validation}", filter.id)]
+    InvalidItemFilter {
+        validation: ItemFilterValidation,
+        filter: Box<ItemFilter>,
+    },
+    #[error("no access token available")]
+    MissingToken,
+    /// No [`crate::transport::Transport`] was configured, which is required
+    /// without the `reqwest-transport` feature.
+    #[error("no transport configured")]
+    MissingTransport,
+    #[error("authorization failed: {0}")]
+    OAuth(OAuthError),
+    #[error("transport error")]
+    Transport(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
     #[error("deserialization error")]
     Serde(#[from] serde_json::Error),
     #[error("unknown")]
@@ -21,11 +83,40 @@ impl From<ApiError> for PoeError {
     fn from(err: ApiError) -> PoeError {
         match err.code {
             1 => PoeError::NotFound(err),
+            2 => PoeError::InvalidQuery(err),
+            3 => PoeError::RateLimitExceeded(err),
+            4 => PoeError::InternalError(err),
+            5 => PoeError::UnexpectedContentType(err),
+            6 => PoeError::Forbidden(err),
+            7 => PoeError::TemporarilyUnavailable(err),
+            8 => PoeError::Unauthorized(err),
+            9 => PoeError::MethodNotAllowed(err),
+            10 => PoeError::UnprocessableEntity(err),
             _ => PoeError::UnknownApiError(err),
         }
     }
 }
 
+impl PoeError {
+    /// The error returned by the API, if any.
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            PoeError::NotFound(err)
+            | PoeError::InvalidQuery(err)
+            | PoeError::RateLimitExceeded(err)
+            | PoeError::InternalError(err)
+            | PoeError::UnexpectedContentType(err)
+            | PoeError::Forbidden(err)
+            | PoeError::TemporarilyUnavailable(err)
+            | PoeError::Unauthorized(err)
+            | PoeError::MethodNotAllowed(err)
+            | PoeError::UnprocessableEntity(err)
+            | PoeError::UnknownApiError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
 pub type PoeResult<T> = Result<T, PoeError>;
 
 #[derive(Debug, Deserialize)]
@@ -37,4 +128,90 @@ pub struct ApiErrorResponse {
 pub struct ApiError {
     pub code: i32,
     pub message: String,
+    /// HTTP status of the response, filled in by the client.
+    #[serde(skip)]
+    pub status: u16,
+    /// URL of the failed request, filled in by the client.
+    #[serde(skip)]
+    pub url: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (code {}, status {}, {})",
+            self.message, self.code, self.status, self.url
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_error(code: i32) -> ApiError {
+        ApiError {
+            code,
+            message: "Invalid query".to_string(),
+            status: 400,
+            url: "https://api.pathofexile.com/ladders/Foo".to_string(),
+        }
+    }
+
+    #[test]
+    fn api_error_codes() {
+        assert!(matches!(
+            PoeError::from(api_error(1)),
+            PoeError::NotFound(_)
+        ));
+        assert!(matches!(
+            PoeError::from(api_error(2)),
+            PoeError::InvalidQuery(_)
+        ));
+        assert!(matches!(
+            PoeError::from(api_error(3)),
+            PoeError::RateLimitExceeded(_)
+        ));
+        assert!(matches!(
+            PoeError::from(api_error(4)),
+            PoeError::InternalError(_)
+        ));
+        assert!(matches!(
+            PoeError::from(api_error(5)),
+            PoeError::UnexpectedContentType(_)
+        ));
+        assert!(matches!(
+            PoeError::from(api_error(6)),
+            PoeError::Forbidden(_)
+        ));
+        assert!(matches!(
+            PoeError::from(api_error(7)),
+            PoeError::TemporarilyUnavailable(_)
+        ));
+        assert!(matches!(
+            PoeError::from(api_error(8)),
+            PoeError::Unauthorized(_)
+        ));
+        assert!(matches!(
+            PoeError::from(api_error(9)),
+            PoeError::MethodNotAllowed(_)
+        ));
+        assert!(matches!(
+            PoeError::from(api_error(10)),
+            PoeError::UnprocessableEntity(_)
+        ));
+        assert!(matches!(
+            PoeError::from(api_error(42)),
+            PoeError::UnknownApiError(_)
+        ));
+    }
+
+    #[test]
+    fn api_error_display() {
+        assert_eq!(
+            "invalid query: Invalid query (code 2, status 400, https://api.pathofexile.com/ladders/Foo)",
+            PoeError::from(api_error(2)).to_string()
+        );
+    }
 }
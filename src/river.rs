@@ -0,0 +1,295 @@
+//! Resumable consumption of the public stash tab river.
+//!
+//! The [`PublicStashConsumer`] persists the `next_change_id` of every handled batch in a
+//! [`CheckpointStore`], a restarted consumer continues with the first batch which has not
+//! been handled yet:
+//!
+//! ```no_run
+//! # async fn run() -> poe_api::PoeResult<()> {
+//! use poe_api::river::{FileCheckpointStore, PublicStashConsumer};
+//! use poe_api::PathOfExile;
+//!
+//! let poe = PathOfExile::builder()
+//!     .token(poe_api::oauth::TokenSet::new("access token"))
+//!     .build()?;
+//!
+//! PublicStashConsumer::new(poe, FileCheckpointStore::new("river.checkpoint"))
+//!     .on_checkpoint(|change_id| println!("handled everything up to {change_id}"))
+//!     .run(|batch| async move {
+//!         println!("{} stashes changed", batch.stashes.len());
+//!         Ok(())
+//!     })
+//!     .await
+//! # }
+//! ```
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use std::fmt;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::api::PublicStashesResponse;
+use crate::api_client::PathOfExile;
+use crate::response::PoeResult;
+use crate::utils::write_file_atomic;
+
+/// Persistent storage for the last handled change id of the public stash tab river.
+pub trait CheckpointStore: Send + Sync {
+    /// Loads the stored change id, `None` if nothing has been handled yet.
+    fn load(&self) -> BoxFuture<'_, PoeResult<Option<String>>>;
+
+    /// Replaces the stored change id.
+    fn store<'a>(&'a self, change_id: &'a str) -> BoxFuture<'a, PoeResult<()>>;
+}
+
+impl<T: CheckpointStore + ?Sized> CheckpointStore for std::sync::Arc<T> {
+    fn load(&self) -> BoxFuture<'_, PoeResult<Option<String>>> {
+        (**self).load()
+    }
+
+    fn store<'a>(&'a self, change_id: &'a str) -> BoxFuture<'a, PoeResult<()>> {
+        (**self).store(change_id)
+    }
+}
+
+/// A [`CheckpointStore`] which only keeps the change id in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryCheckpointStore {
+    change_id: Mutex<Option<String>>,
+}
+
+impl InMemoryCheckpointStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a store which already contains a change id.
+    pub fn with_checkpoint(change_id: impl Into<String>) -> Self {
+        Self {
+            change_id: Mutex::new(Some(change_id.into())),
+        }
+    }
+
+    /// Returns the currently stored change id.
+    pub fn get(&self) -> Option<String> {
+        self.change_id.lock().unwrap().clone()
+    }
+}
+
+impl CheckpointStore for InMemoryCheckpointStore {
+    fn load(&self) -> BoxFuture<'_, PoeResult<Option<String>>> {
+        Box::pin(async move { Ok(self.get()) })
+    }
+
+    fn store<'a>(&'a self, change_id: &'a str) -> BoxFuture<'a, PoeResult<()>> {
+        Box::pin(async move {
+            *self.change_id.lock().unwrap() = Some(change_id.to_string());
+            Ok(())
+        })
+    }
+}
+
+/// A [`CheckpointStore`] which keeps the change id in a plain text file.
+///
+/// The file is replaced atomically and synced to disk before [`CheckpointStore::store`] returns.
+#[derive(Debug, Clone)]
+pub struct FileCheckpointStore {
+    path: PathBuf,
+}
+
+impl FileCheckpointStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+    fn load(&self) -> BoxFuture<'_, PoeResult<Option<String>>> {
+        Box::pin(async move {
+            match tokio::fs::read_to_string(&self.path).await {
+                Ok(content) if content.trim().is_empty() => Ok(None),
+                Ok(content) => Ok(Some(content.trim().to_string())),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        })
+    }
+
+    fn store<'a>(&'a self, change_id: &'a str) -> BoxFuture<'a, PoeResult<()>> {
+        Box::pin(async move {
+            write_file_atomic(&self.path, change_id.as_bytes(), false).await?;
+            Ok(())
+        })
+    }
+}
+
+type CheckpointHook = dyn Fn(&str) + Send + Sync;
+
+/// Consumes the public stash tab river and checkpoints the progress after every batch.
+///
+/// Batches are delivered at least once: the checkpoint is only stored after the handler
+/// succeeded, a batch whose handling was interrupted is delivered again after a restart.
+pub struct PublicStashConsumer<S> {
+    poe: PathOfExile,
+    store: S,
+    start: Option<String>,
+    on_checkpoint: Option<Box<CheckpointHook>>,
+}
+
+impl<S: CheckpointStore> PublicStashConsumer<S> {
+    pub fn new(poe: PathOfExile, store: S) -> Self {
+        Self {
+            poe,
+            store,
+            start: None,
+            on_checkpoint: None,
+        }
+    }
+
+    /// Sets the change id to start at if the store does not contain a checkpoint yet.
+    /// Defaults to the beginning of the river.
+    pub fn start_at(mut self, change_id: impl Into<String>) -> Self {
+        self.start = Some(change_id.into());
+        self
+    }
+
+    /// Sets a hook which is called with the `next_change_id` of every batch,
+    /// after the batch has been handled and the checkpoint has been stored.
+    pub fn on_checkpoint(mut self, hook: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.on_checkpoint = Some(Box::new(hook));
+        self
+    }
+
+    /// Returns the checkpoint store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Handles batches from the last checkpoint onwards, until a request or the handler fails.
+    pub async fn run<H, Fut>(&self, mut handler: H) -> PoeResult<()>
+    where
+        H: FnMut(PublicStashesResponse) -> Fut,
+        Fut: Future<Output = PoeResult<()>>,
+    {
+        let start = match self.store.load().await? {
+            Some(change_id) => Some(change_id),
+            None => self.start.clone(),
+        };
+
+        let stream = self.poe.public_stash_stream(start);
+        futures::pin_mut!(stream);
+
+        while let Some(batch) = stream.next().await {
+            let batch = batch?;
+            let change_id = batch.next_change_id.clone();
+
+            handler(batch).await?;
+            self.store.store(&change_id).await?;
+
+            if let Some(hook) = self.on_checkpoint.as_ref() {
+                hook(&change_id);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for PublicStashConsumer<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PublicStashConsumer")
+            .field("store", &self.store)
+            .field("start", &self.start)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::PoeError;
+    use crate::transport::{InMemoryTransport, Response};
+    use std::sync::Arc;
+
+    fn river() -> (PathOfExile, Arc<InMemoryTransport>) {
+        let transport = Arc::new(InMemoryTransport::new(|request| {
+            let next = match request.uri().query() {
+                None => "1",
+                Some("id=1") => "2",
+                Some("id=2") => "3",
+                _ => "4",
+            };
+            let body = serde_json::json!({
+                "next_change_id": next,
+                "stashes": [{ "id": next, "public": true, "stashType": "PremiumStash" }],
+            });
+            Response::new(serde_json::to_vec(&body).unwrap())
+        }));
+
+        let poe = PathOfExile::builder()
+            .transport(transport.clone())
+            .build()
+            .unwrap();
+        (poe, transport)
+    }
+
+    #[tokio::test]
+    async fn resume_after_failure() {
+        let (poe, transport) = river();
+        let store = Arc::new(InMemoryCheckpointStore::new());
+        let checkpoints = Arc::new(Mutex::new(Vec::new()));
+
+        let hook_checkpoints = checkpoints.clone();
+        let consumer = PublicStashConsumer::new(poe.clone(), store.clone())
+            .on_checkpoint(move |id| hook_checkpoints.lock().unwrap().push(id.to_string()));
+
+        // the handler fails on the second batch, which must not be checkpointed
+        let result = consumer
+            .run(|batch| async move {
+                match batch.next_change_id.as_str() {
+                    "1" => Ok(()),
+                    _ => Err(PoeError::Unknown),
+                }
+            })
+            .await;
+        assert!(matches!(result, Err(PoeError::Unknown)));
+        assert_eq!(Some("1".to_string()), store.get());
+        assert_eq!(vec!["1".to_string()], *checkpoints.lock().unwrap());
+
+        let consumer = PublicStashConsumer::new(poe, store.clone()).start_at("3");
+        let result = consumer
+            .run(|batch| async move {
+                match batch.next_change_id.as_str() {
+                    "2" => Ok(()),
+                    _ => Err(PoeError::Unknown),
+                }
+            })
+            .await;
+        assert!(matches!(result, Err(PoeError::Unknown)));
+        assert_eq!(Some("2".to_string()), store.get());
+
+        let requests = transport.requests();
+        assert_eq!(None, requests[0].url.query());
+        assert_eq!(Some("id=1"), requests[1].url.query());
+        // resumed at the checkpoint, the start change id is only a fallback
+        assert_eq!(Some("id=1"), requests[2].url.query());
+        assert_eq!(Some("id=2"), requests[3].url.query());
+    }
+
+    #[tokio::test]
+    async fn file_checkpoint_store() {
+        let path = std::env::temp_dir().join(format!("poe-rs-checkpoint-{}", std::process::id()));
+        let store = FileCheckpointStore::new(&path);
+        assert_eq!(None, store.load().await.unwrap());
+
+        store.store("123-456-789").await.unwrap();
+        assert_eq!(Some("123-456-789".to_string()), store.load().await.unwrap());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,655 @@
+//! Models of the trade site API.
+//!
+//! Searches are built with [`TradeQuery`]:
+//!
+//! ```
+//! use poe_api::trade::{StatGroup, TradeQuery, TradeStatus, ValueRange};
+//!
+//! let query = TradeQuery::new()
+//!     .status(TradeStatus::Any)
+//!     .item_type("Vaal Regalia")
+//!     .stats(StatGroup::and().filter("pseudo.pseudo_total_life", ValueRange::min(70.0)))
+//!     .links(ValueRange::min(6.0))
+//!     .price(ValueRange::max(50.0), "chaos");
+//! ```
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::api::{Item, Map, Realm};
+use crate::utils::is_false;
+
+/// Which sellers to include in a search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeStatus {
+    Online,
+    /// Online and in the league of the search.
+    OnlineLeague,
+    Any,
+}
+
+/// A search on the trade site.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeQuery {
+    query: Query,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    sort: Map<String, String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Query {
+    status: OptionValue<TradeStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    item_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    stats: Vec<StatGroup>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    filters: Map<String, FilterGroup>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct OptionValue<T> {
+    option: T,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct FilterGroup {
+    filters: Map<String, Value>,
+}
+
+impl Default for TradeQuery {
+    fn default() -> Self {
+        let mut sort = Map::new();
+        sort.insert("price".to_string(), "asc".to_string());
+
+        Self {
+            query: Query {
+                status: OptionValue {
+                    option: TradeStatus::Online,
+                },
+                name: None,
+                item_type: None,
+                stats: Vec::new(),
+                filters: Map::new(),
+            },
+            sort,
+        }
+    }
+}
+
+impl TradeQuery {
+    /// Creates a search for online sellers, sorted by ascending price.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn status(mut self, status: TradeStatus) -> Self {
+        self.query.status.option = status;
+        self
+    }
+
+    /// Only includes items with the name, e.g. of a unique item.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.query.name = Some(name.into());
+        self
+    }
+
+    /// Only includes items of the base type.
+    pub fn item_type(mut self, item_type: impl Into<String>) -> Self {
+        self.query.item_type = Some(item_type.into());
+        self
+    }
+
+    /// Adds a group of stat filters, can be called multiple times.
+    pub fn stats(mut self, group: StatGroup) -> Self {
+        self.query.stats.push(group);
+        self
+    }
+
+    /// Sets a filter of a filter group, e.g. `misc_filters` and `ilvl`.
+    ///
+    /// The typed helpers like [`TradeQuery::sockets`] should be preferred,
+    /// this allows to use filters which are not supported by them.
+    pub fn filter(
+        mut self,
+        group: impl Into<String>,
+        name: impl Into<String>,
+        value: impl Serialize,
+    ) -> Self {
+        let value = serde_json::to_value(value).expect("filter values are always valid JSON");
+        self.query
+            .filters
+            .entry(group.into())
+            .or_default()
+            .filters
+            .insert(name.into(), value);
+        self
+    }
+
+    /// Filters by the number of sockets.
+    pub fn sockets(self, range: ValueRange) -> Self {
+        self.filter("socket_filters", "sockets", range)
+    }
+
+    /// Filters by the size of the largest link group.
+    pub fn links(self, range: ValueRange) -> Self {
+        self.filter("socket_filters", "links", range)
+    }
+
+    /// Filters by a numeric misc filter, e.g. `ilvl`, `quality` or `gem_level`.
+    pub fn misc(self, name: impl Into<String>, range: ValueRange) -> Self {
+        self.filter("misc_filters", name, range)
+    }
+
+    /// Filters by a boolean misc filter, e.g. `corrupted`, `identified` or `mirrored`.
+    pub fn misc_flag(self, name: impl Into<String>, value: bool) -> Self {
+        let option = if value { "true" } else { "false" };
+        self.filter("misc_filters", name, OptionValue { option })
+    }
+
+    /// Filters by the listed price in a currency, e.g. `chaos` or `divine`.
+    pub fn price(self, range: ValueRange, currency: impl Into<String>) -> Self {
+        let mut value = serde_json::to_value(range).expect("ranges are always valid JSON");
+        value["option"] = Value::String(currency.into());
+        self.filter("trade_filters", "price", value)
+    }
+
+    /// Sorts the results by a key, e.g. `price` or a stat id. Replaces the default price sort.
+    pub fn sort(mut self, key: impl Into<String>, ascending: bool) -> Self {
+        let order = if ascending { "asc" } else { "desc" };
+        self.sort.clear();
+        self.sort.insert(key.into(), order.to_string());
+        self
+    }
+}
+
+/// How the filters of a [`StatGroup`] are combined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatGroupType {
+    And,
+    Not,
+    If,
+    Count,
+    Weight,
+}
+
+/// A group of stat filters.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StatGroup {
+    #[serde(rename = "type")]
+    pub group_type: StatGroupType,
+    pub filters: Vec<StatFilter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<ValueRange>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub disabled: bool,
+}
+
+impl StatGroup {
+    fn new(group_type: StatGroupType, value: Option<ValueRange>) -> Self {
+        Self {
+            group_type,
+            filters: Vec::new(),
+            value,
+            disabled: false,
+        }
+    }
+
+    /// Items must match all filters.
+    pub fn and() -> Self {
+        Self::new(StatGroupType::And, None)
+    }
+
+    /// Items must match none of the filters.
+    pub fn not() -> Self {
+        Self::new(StatGroupType::Not, None)
+    }
+
+    /// Items must match the filters they have stats for.
+    pub fn when() -> Self {
+        Self::new(StatGroupType::If, None)
+    }
+
+    /// Items must match a number of filters within the range.
+    pub fn count(range: ValueRange) -> Self {
+        Self::new(StatGroupType::Count, Some(range))
+    }
+
+    /// The weighted sum of the stats of an item must be within the range.
+    pub fn weight(range: ValueRange) -> Self {
+        Self::new(StatGroupType::Weight, Some(range))
+    }
+
+    /// Adds a filter for a stat id, e.g. `explicit.stat_3299347043`.
+    pub fn filter(mut self, id: impl Into<String>, value: ValueRange) -> Self {
+        self.filters.push(StatFilter {
+            id: id.into(),
+            value: Some(value),
+            disabled: false,
+        });
+        self
+    }
+
+    /// Adds a filter for a stat id which only requires the stat to be present.
+    pub fn has(mut self, id: impl Into<String>) -> Self {
+        self.filters.push(StatFilter {
+            id: id.into(),
+            value: None,
+            disabled: false,
+        });
+        self
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StatFilter {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<ValueRange>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub disabled: bool,
+}
+
+/// A range of values, with a weight for [`StatGroupType::Weight`] groups.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValueRange {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f64>,
+}
+
+impl ValueRange {
+    pub fn min(min: f64) -> Self {
+        Self {
+            min: Some(min),
+            ..Self::default()
+        }
+    }
+
+    pub fn max(max: f64) -> Self {
+        Self {
+            max: Some(max),
+            ..Self::default()
+        }
+    }
+
+    pub fn between(min: f64, max: f64) -> Self {
+        Self {
+            min: Some(min),
+            max: Some(max),
+            weight: None,
+        }
+    }
+
+    /// Sets the weight of the stat, only used by [`StatGroupType::Weight`] groups.
+    pub fn weighted(mut self, weight: f64) -> Self {
+        self.weight = Some(weight);
+        self
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeSearchResponse {
+    /// Id of the search, required to fetch the results.
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub complexity: Option<u32>,
+    /// Ids of the results, at most 100.
+    pub result: Vec<String>,
+    /// Total number of matching items.
+    pub total: usize,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub inexact: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeFetchResponse {
+    /// Listings in the order of the requested ids, `None` if a listing is gone.
+    pub result: Vec<Option<TradeListing>>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeListing {
+    pub id: String,
+    pub listing: Listing,
+    pub item: Item,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Listing {
+    pub method: String,
+    pub indexed: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stash: Option<ListingStash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub whisper: Option<String>,
+    pub account: ListingAccount,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<ListingPrice>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ListingStash {
+    pub name: String,
+    pub x: u32,
+    pub y: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListingAccount {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_character_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub online: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ListingPrice {
+    /// `~b/o` or `~price`.
+    #[serde(rename = "type")]
+    pub price_type: String,
+    pub amount: f64,
+    pub currency: String,
+}
+
+/// Item names and base types by category, from `/api/trade/data/items`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemCatalog {
+    #[serde(rename = "result")]
+    pub categories: Vec<TradeItemCategory>,
+}
+
+impl ItemCatalog {
+    /// Returns the category of a base type, e.g. `accessory` for `Onyx Amulet`.
+    pub fn category(&self, base_type: &str) -> Option<&TradeItemCategory> {
+        self.categories.iter().find(|category| {
+            category
+                .entries
+                .iter()
+                .any(|entry| entry.item_type == base_type)
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeItemCategory {
+    pub id: String,
+    pub label: String,
+    pub entries: Vec<TradeItemEntry>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeItemEntry {
+    /// Name of unique items.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub item_type: String,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flags: Option<TradeItemFlags>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeItemFlags {
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub unique: bool,
+}
+
+/// Searchable stats by category, from `/api/trade/data/stats`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StatCatalog {
+    #[serde(rename = "result")]
+    pub categories: Vec<TradeStatCategory>,
+}
+
+impl StatCatalog {
+    /// Returns the stat of a category (e.g. `explicit`) which matches a mod text
+    /// like `+45% to Cold Resistance`.
+    pub fn find(&self, category: &str, text: &str) -> Option<&TradeStat> {
+        let template = stat_template(text);
+        self.categories
+            .iter()
+            .filter(|c| c.id == category)
+            .flat_map(|c| c.entries.iter())
+            .find(|stat| stat.text == template)
+    }
+
+    /// Translates a mod text of a category into a filter, with the value of the mod as minimum.
+    ///
+    /// Mods with multiple values, e.g. `Adds 5 to 10 Fire Damage`, use the average
+    /// like the trade site does.
+    pub fn filter(&self, category: &str, text: &str) -> Option<StatFilter> {
+        let stat = self.find(category, text)?;
+        let values = stat_values(text);
+        let value = if values.is_empty() {
+            None
+        } else {
+            Some(ValueRange::min(
+                values.iter().sum::<f64>() / values.len() as f64,
+            ))
+        };
+
+        Some(StatFilter {
+            id: stat.id.clone(),
+            value,
+            disabled: false,
+        })
+    }
+}
+
+/// Replaces all numbers of a mod text with `#`, which is how the trade site
+/// stores stat texts.
+fn stat_template(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut template = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].is_ascii_digit() {
+            // consume the whole number, including decimals but not a trailing full stop
+            while i < chars.len()
+                && (chars[i].is_ascii_digit()
+                    || (chars[i] == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit)))
+            {
+                i += 1;
+            }
+            template.push('#');
+        } else {
+            template.push(chars[i]);
+            i += 1;
+        }
+    }
+    template
+}
+
+/// Extracts all numbers of a mod text.
+fn stat_values(text: &str) -> Vec<f64> {
+    text.split(|c: char| !c.is_ascii_digit() && c != '.')
+        .filter_map(|number| number.parse().ok())
+        .collect()
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeStatCategory {
+    pub id: String,
+    pub label: String,
+    pub entries: Vec<TradeStat>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeStat {
+    pub id: String,
+    pub text: String,
+    #[serde(rename = "type")]
+    pub stat_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub option: Option<TradeStatOptions>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeStatOptions {
+    pub options: Vec<TradeStatOption>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeStatOption {
+    pub id: Value,
+    pub text: String,
+}
+
+/// Currencies, fragments and other exchangeable items by category, from `/api/trade/data/static`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StaticCatalog {
+    #[serde(rename = "result")]
+    pub categories: Vec<TradeStaticCategory>,
+}
+
+impl StaticCatalog {
+    /// Returns the trade id of an item by its name, e.g. `alt` for `Orb of Alteration`.
+    pub fn id(&self, text: &str) -> Option<&str> {
+        self.categories
+            .iter()
+            .flat_map(|category| category.entries.iter())
+            .find(|entry| entry.text == text)
+            .map(|entry| entry.id.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeStaticCategory {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub entries: Vec<TradeStaticEntry>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeStaticEntry {
+    pub id: String,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeLeaguesResponse {
+    pub result: Vec<TradeLeague>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TradeLeague {
+    pub id: String,
+    #[serde(default)]
+    pub realm: Realm,
+    pub text: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trade_query() {
+        let query = TradeQuery::new()
+            .name("Headhunter")
+            .item_type("Leather Belt")
+            .stats(StatGroup::weight(ValueRange::min(100.0)).filter(
+                "pseudo.pseudo_total_life",
+                ValueRange::default().weighted(1.0),
+            ))
+            .stats(StatGroup::count(ValueRange::min(1.0)).has("explicit.stat_1"))
+            .sockets(ValueRange::between(1.0, 2.0))
+            .misc_flag("corrupted", false)
+            .price(ValueRange::max(50.0), "divine")
+            .sort("price", false);
+
+        assert_eq!(
+            serde_json::json!({
+                "query": {
+                    "status": { "option": "online" },
+                    "name": "Headhunter",
+                    "type": "Leather Belt",
+                    "stats": [{
+                        "type": "weight",
+                        "filters": [{ "id": "pseudo.pseudo_total_life", "value": { "weight": 1.0 } }],
+                        "value": { "min": 100.0 },
+                    }, {
+                        "type": "count",
+                        "filters": [{ "id": "explicit.stat_1" }],
+                        "value": { "min": 1.0 },
+                    }],
+                    "filters": {
+                        "socket_filters": { "filters": { "sockets": { "min": 1.0, "max": 2.0 } } },
+                        "misc_filters": { "filters": { "corrupted": { "option": "false" } } },
+                        "trade_filters": {
+                            "filters": { "price": { "max": 50.0, "option": "divine" } },
+                        },
+                    },
+                },
+                "sort": { "price": "desc" },
+            }),
+            serde_json::to_value(&query).unwrap()
+        );
+    }
+
+    #[test]
+    fn stat_lookup() {
+        let stats: StatCatalog = serde_json::from_value(serde_json::json!({
+            "result": [{
+                "id": "explicit",
+                "label": "Explicit",
+                "entries": [{
+                    "id": "explicit.stat_4220027924",
+                    "text": "+#% to Cold Resistance",
+                    "type": "explicit",
+                }, {
+                    "id": "explicit.stat_1573130764",
+                    "text": "Adds # to # Fire Damage to Attacks",
+                    "type": "explicit",
+                }],
+            }],
+        }))
+        .unwrap();
+
+        assert_eq!(
+            "explicit.stat_4220027924",
+            stats
+                .find("explicit", "+45% to Cold Resistance")
+                .unwrap()
+                .id
+        );
+        assert!(stats.find("implicit", "+45% to Cold Resistance").is_none());
+
+        let filter = stats
+            .filter("explicit", "Adds 5 to 10 Fire Damage to Attacks")
+            .unwrap();
+        assert_eq!("explicit.stat_1573130764", filter.id);
+        assert_eq!(Some(ValueRange::min(7.5)), filter.value);
+    }
+
+    #[test]
+    fn stat_template() {
+        assert_eq!(
+            "+#% to Cold Resistance",
+            super::stat_template("+45% to Cold Resistance")
+        );
+        assert_eq!("# and #.", super::stat_template("1.5 and 10."));
+        assert_eq!(
+            vec![1.5, 10.0],
+            super::stat_values("Regenerate 1.5 Life, 10 Mana")
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! HTTP transports used to send requests to the API.
+//!
+//! By default requests are sent with `ReqwestTransport`, a custom [`Transport`] can be
+//! configured with [`crate::PathOfExileBuilder::transport`]. Without the `reqwest-transport`
+//! feature the client does not depend on reqwest and a transport has to be configured.
+//!
+//! The [`InMemoryTransport`] answers requests without any network access, which is useful for tests:
+//!
+//! ```
+//! # async fn run() -> poe_api::PoeResult<()> {
+//! use poe_api::transport::{InMemoryTransport, Response};
+//! use poe_api::PathOfExile;
+//!
+//! let transport = InMemoryTransport::new(|_request| {
+//!     Response::new(br#"{"total": 0, "cached_since": "2021-01-01T00:00:00Z", "entries": []}"#.to_vec())
+//! });
+//!
+//! let poe = PathOfExile::builder().transport(transport).build()?;
+//! let ladder = poe.ladder("Standard", 10, 0).await?;
+//! assert_eq!(0, ladder.total);
+//! # Ok(())
+//! # }
+//! ```
+use futures::future::BoxFuture;
+use http::{HeaderMap, Method};
+#[cfg(feature = "reqwest-transport")]
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+use crate::response::{PoeError, PoeResult};
+
+pub use http::StatusCode;
+
+/// A request to be sent by a [`Transport`].
+pub type Request = http::Request<Vec<u8>>;
+
+/// A response received by a [`Transport`].
+pub type Response = http::Response<Vec<u8>>;
+
+/// Sends HTTP requests.
+pub trait Transport: Send + Sync {
+    fn send(&self, request: Request) -> BoxFuture<'_, PoeResult<Response>>;
+}
+
+/// The transport used when none is configured.
+#[cfg(feature = "reqwest-transport")]
+pub(crate) fn default_transport() -> Arc<dyn Transport> {
+    Arc::new(ReqwestTransport::default())
+}
+
+/// The transport used when none is configured, every request fails with
+/// [`PoeError::MissingTransport`].
+#[cfg(not(feature = "reqwest-transport"))]
+pub(crate) fn default_transport() -> Arc<dyn Transport> {
+    struct MissingTransport;
+
+    impl Transport for MissingTransport {
+        fn send(&self, _request: Request) -> BoxFuture<'_, PoeResult<Response>> {
+            Box::pin(async { Err(PoeError::MissingTransport) })
+        }
+    }
+
+    Arc::new(MissingTransport)
+}
+
+/// A [`Transport`] backed by a [`reqwest::Client`].
+#[cfg(feature = "reqwest-transport")]
+#[derive(Clone, Debug, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "reqwest-transport")]
+impl ReqwestTransport {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[cfg(feature = "reqwest-transport")]
+impl From<reqwest::Client> for ReqwestTransport {
+    fn from(client: reqwest::Client) -> Self {
+        Self::new(client)
+    }
+}
+
+#[cfg(feature = "reqwest-transport")]
+impl Transport for ReqwestTransport {
+    fn send(&self, request: Request) -> BoxFuture<'_, PoeResult<Response>> {
+        Box::pin(async move {
+            let request = reqwest::Request::try_from(request)?;
+            let response = self.client.execute(request).await?;
+
+            let mut builder = http::Response::builder()
+                .status(response.status())
+                .version(response.version());
+            if let Some(headers) = builder.headers_mut() {
+                *headers = response.headers().clone();
+            }
+
+            let body = response.bytes().await?;
+            Ok(builder
+                .body(body.to_vec())
+                .expect("response parts are always valid"))
+        })
+    }
+}
+
+/// A request recorded by the [`InMemoryTransport`].
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+type Handler = dyn Fn(&Request) -> Response + Send + Sync;
+
+/// A [`Transport`] which answers requests with a handler function, without any network access.
+///
+/// All requests are recorded and can be inspected with [`InMemoryTransport::requests`].
+pub struct InMemoryTransport {
+    handler: Box<Handler>,
+    requests: Mutex<Vec<RecordedRequest>>,
+}
+
+impl InMemoryTransport {
+    pub fn new(handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> Self {
+        Self {
+            handler: Box::new(handler),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns all requests received so far.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl fmt::Debug for InMemoryTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InMemoryTransport")
+            .field("requests", &self.requests)
+            .finish()
+    }
+}
+
+impl Transport for InMemoryTransport {
+    fn send(&self, request: Request) -> BoxFuture<'_, PoeResult<Response>> {
+        Box::pin(async move {
+            let url = Url::parse(&request.uri().to_string())
+                .map_err(|err| PoeError::Transport(Box::new(err)))?;
+
+            self.requests.lock().unwrap().push(RecordedRequest {
+                method: request.method().clone(),
+                url,
+                headers: request.headers().clone(),
+                body: request.body().clone(),
+            });
+
+            Ok((self.handler)(&request))
+        })
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn send(&self, request: Request) -> BoxFuture<'_, PoeResult<Response>> {
+        (**self).send(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn in_memory_transport() {
+        let transport = InMemoryTransport::new(|request| {
+            http::Response::builder()
+                .status(StatusCode::CREATED)
+                .body(request.body().clone())
+                .unwrap()
+        });
+
+        let request = http::Request::post("https://api.pathofexile.com/foo?bar=1")
+            .header("User-Agent", "test")
+            .body(b"body".to_vec())
+            .unwrap();
+        let response = transport.send(request).await.unwrap();
+        assert_eq!(StatusCode::CREATED, response.status());
+        assert_eq!(b"body", response.body().as_slice());
+
+        let requests = transport.requests();
+        assert_eq!(1, requests.len());
+        assert_eq!(Method::POST, requests[0].method);
+        assert_eq!("/foo", requests[0].url.path());
+        assert_eq!(Some("bar=1"), requests[0].url.query());
+        assert_eq!("test", requests[0].headers["User-Agent"]);
+    }
+}
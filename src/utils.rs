@@ -3,6 +3,8 @@ use serde::{Deserialize, Deserializer};
 use std::collections::HashMap;
 use std::fmt;
 use std::marker::PhantomData;
+#[cfg(feature = "client-core")]
+use std::path::Path;
 
 pub(crate) fn is_false(b: &bool) -> bool {
     !b
@@ -110,6 +112,59 @@ where
     deserializer.deserialize_seq(NumVisitor)
 }
 
+/// Replaces the content of a file, such that after a crash either the old or the new content
+/// is on disk, but never a partially written file.
+///
+/// With `private` the file is only accessible by its owner (on Unix).
+#[cfg(feature = "client-core")]
+pub(crate) async fn write_file_atomic(
+    path: &Path,
+    content: &[u8],
+    private: bool,
+) -> std::io::Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(&tmp).await?;
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::PermissionsExt;
+        // a leftover temporary file keeps its previous permissions
+        let permissions = std::fs::Permissions::from_mode(0o600);
+        file.set_permissions(permissions).await?;
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+
+    file.write_all(content).await?;
+    // the content has to be on disk before the rename makes it visible
+    file.sync_all().await?;
+    drop(file);
+
+    tokio::fs::rename(&tmp, path).await?;
+
+    // the rename itself is only durable once the directory is synced
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        tokio::fs::File::open(dir).await?.sync_all().await?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -164,4 +219,24 @@ mod test {
         let a = serde_json::from_str::<StringU32>("{\"foo\": 12}").unwrap();
         assert_eq!(12, a.foo);
     }
+
+    #[cfg(feature = "client-core")]
+    #[tokio::test]
+    async fn write_file_atomic_replaces_content() {
+        let path = std::env::temp_dir().join(format!("poe-rs-atomic-{}", std::process::id()));
+        write_file_atomic(&path, b"old content", false)
+            .await
+            .unwrap();
+        write_file_atomic(&path, b"new", true).await.unwrap();
+
+        assert_eq!(b"new".to_vec(), std::fs::read(&path).unwrap());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(0o600, mode & 0o777);
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
 }